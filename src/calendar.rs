@@ -0,0 +1,333 @@
+use crate::clock::{Date, DateKind};
+use crate::diagnostics::Diagnostics;
+use crate::rules::Rules;
+use crate::table::read_rows;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::io::Read;
+
+pub const FILE_NAME: &str = "休日.csv";
+
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    pub offs: Vec<Date>,
+    pub national_holidays: bool,
+    pub weekly_offs: Vec<(Weekday, DateKind)>,
+}
+
+impl Calendar {
+    pub fn new(
+        offs: Vec<Date>,
+        national_holidays: bool,
+        weekly_offs: Vec<(Weekday, DateKind)>,
+    ) -> Self {
+        Self {
+            offs,
+            national_holidays,
+            weekly_offs,
+        }
+    }
+
+    // 休日.csvの指定（年付きの日付を優先）を使い、無ければ曜日ごとの休日、国民の祝日の順に判定する
+    pub fn date_kind(&self, date: &NaiveDate) -> DateKind {
+        let found = self
+            .offs
+            .iter()
+            .find(|o| o.raw_date.year.is_some() && o.raw_date.matches(date))
+            .or_else(|| self.offs.iter().find(|o| o.raw_date.matches(date)));
+
+        match found {
+            Some(o) => match o.date_type {
+                DateKind::Unknown => DateKind::CompanyOff,
+                _ => o.date_type.clone(),
+            },
+            None => match self.weekly_offs.iter().find(|(w, _)| *w == date.weekday()) {
+                Some((_, kind)) => kind.clone(),
+                None if self.national_holidays && is_national_holiday(date) => DateKind::CompanyOff,
+                None => DateKind::On,
+            },
+        }
+    }
+}
+
+pub fn is_national_holiday(date: &NaiveDate) -> bool {
+    national_holidays(date.year()).contains(date)
+}
+
+// 国民の祝日（振替休日・国民の休日を含む）。春分・秋分の計算式が使える1980〜2099年に対応
+pub fn national_holidays(year: i32) -> Vec<NaiveDate> {
+    if !(1980..=2099).contains(&year) {
+        return vec![];
+    }
+
+    let mut holidays: Vec<NaiveDate> = [
+        Some(ymd(year, 1, 1)),
+        Some(match year >= 2000 {
+            true => nth_weekday(year, 1, Weekday::Mon, 2),
+            false => ymd(year, 1, 15),
+        }),
+        Some(ymd(year, 2, 11)),
+        match year {
+            y if y >= 2020 => Some(ymd(year, 2, 23)),
+            1989..=2018 => Some(ymd(year, 12, 23)),
+            _ => None,
+        },
+        Some(ymd(year, 3, vernal_equinox_day(year))),
+        Some(ymd(year, 4, 29)),
+        Some(ymd(year, 5, 3)),
+        match year >= 2007 {
+            true => Some(ymd(year, 5, 4)),
+            false => None,
+        },
+        Some(ymd(year, 5, 5)),
+        match year {
+            2020 => Some(ymd(year, 7, 23)),
+            2021 => Some(ymd(year, 7, 22)),
+            y if y >= 2003 => Some(nth_weekday(year, 7, Weekday::Mon, 3)),
+            1996..=2002 => Some(ymd(year, 7, 20)),
+            _ => None,
+        },
+        match year {
+            2020 => Some(ymd(year, 8, 10)),
+            2021 => Some(ymd(year, 8, 8)),
+            y if y >= 2016 => Some(ymd(year, 8, 11)),
+            _ => None,
+        },
+        Some(match year >= 2003 {
+            true => nth_weekday(year, 9, Weekday::Mon, 3),
+            false => ymd(year, 9, 15),
+        }),
+        Some(ymd(year, 9, autumnal_equinox_day(year))),
+        Some(match year {
+            2020 => ymd(year, 7, 24),
+            2021 => ymd(year, 7, 23),
+            y if y >= 2000 => nth_weekday(year, 10, Weekday::Mon, 2),
+            _ => ymd(year, 10, 10),
+        }),
+        Some(ymd(year, 11, 3)),
+        Some(ymd(year, 11, 23)),
+    ]
+    .iter()
+    .flatten()
+    .cloned()
+    .collect();
+
+    // 皇室の慶弔行事などによる一度限りの祝日
+    holidays.extend(
+        [
+            (1989, 2, 24),
+            (1990, 11, 12),
+            (1993, 6, 9),
+            (2019, 4, 30),
+            (2019, 5, 1),
+            (2019, 5, 2),
+            (2019, 10, 22),
+        ]
+        .iter()
+        .filter(|(y, _, _)| *y == year)
+        .map(|(y, m, d)| ymd(*y, *m, *d)),
+    );
+
+    // 国民の休日：祝日に挟まれた日（日曜日を除く）。1986年から
+    let mut sandwiched: Vec<NaiveDate> = holidays
+        .iter()
+        .map(|h| *h + Duration::days(1))
+        .filter(|d| {
+            year >= 1986
+                && !holidays.contains(d)
+                && holidays.contains(&(*d + Duration::days(1)))
+                && d.weekday() != Weekday::Sun
+        })
+        .collect();
+
+    // 振替休日：日曜日の祝日の後の、最も近い祝日でない日（2006年までは翌日のみ）
+    let mut substitutes: Vec<NaiveDate> = vec![];
+    for h in holidays.iter().filter(|h| h.weekday() == Weekday::Sun) {
+        let mut d = *h + Duration::days(1);
+        while year >= 2007 && holidays.contains(&d) {
+            d += Duration::days(1);
+        }
+        if !holidays.contains(&d) {
+            substitutes.push(d);
+        }
+    }
+
+    holidays.append(&mut sandwiched);
+    holidays.append(&mut substitutes);
+    holidays.sort();
+    holidays.dedup();
+    holidays
+}
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("Invalid date")
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, nth: u32) -> NaiveDate {
+    let first = ymd(year, month, 1);
+    let offset = (weekday.num_days_from_sunday() + 7 - first.weekday().num_days_from_sunday()) % 7;
+    first + Duration::days((offset + 7 * (nth - 1)) as i64)
+}
+
+fn vernal_equinox_day(year: i32) -> u32 {
+    let y = (year - 1980) as f64;
+    (20.8431 + 0.242194 * y - (y / 4.).floor()).floor() as u32
+}
+
+fn autumnal_equinox_day(year: i32) -> u32 {
+    let y = (year - 1980) as f64;
+    (23.2488 + 0.242194 * y - (y / 4.).floor()).floor() as u32
+}
+
+// 休日.csv：日付の後に区分（法定休日・所定休日）を書ける。区分が無ければ所定休日
+// 「平日」「出勤日」とすると、国民の祝日でも出勤日として扱う
+pub fn collect_from_csv<R: Read>(
+    reader: R,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> Calendar {
+    let mut offs: Vec<Date> = vec![];
+    for row in read_rows(reader, FILE_NAME, diagnostics) {
+        let mut ignored = vec![];
+        for field in row
+            .fields
+            .iter()
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+        {
+            if let Ok(mut date) = field.parse::<Date>() {
+                date.date_type = DateKind::CompanyOff;
+                offs.push(date);
+            } else if let Ok(kind) = field.parse::<DateKind>() {
+                if let Some(date) = offs.last_mut() {
+                    date.date_type = kind;
+                }
+            } else {
+                ignored.push(field);
+            }
+        }
+        if !ignored.is_empty() {
+            let reason = format!(
+                "日付でも区分でもないため無視しました：{}",
+                ignored.join(", ")
+            );
+            diagnostics.warn(FILE_NAME, &row, reason);
+        }
+    }
+    Calendar::new(offs, rules.national_holidays, rules.weekly_offs.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{collect_from_csv, national_holidays};
+    use crate::clock::DateKind;
+    use crate::diagnostics::Diagnostics;
+    use crate::rules::Rules;
+    use chrono::NaiveDate;
+    use std::io::Cursor;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn holidays() {
+        let holidays: Vec<String> = national_holidays(2026)
+            .iter()
+            .map(|d| d.format("%m/%d").to_string())
+            .collect();
+        assert_eq!(
+            holidays,
+            vec![
+                "01/01", "01/12", "02/11", "02/23", "03/20", "04/29", "05/03", "05/04", "05/05",
+                "05/06", "07/20", "08/11", "09/21", "09/22", "09/23", "10/12", "11/03", "11/23",
+            ]
+        );
+
+        let holidays = national_holidays(2025);
+        assert!(holidays.contains(&ymd(2025, 2, 24)));
+        assert!(holidays.contains(&ymd(2025, 11, 24)));
+        assert_eq!(holidays.len(), 19);
+
+        let holidays = national_holidays(2019);
+        assert!(holidays.contains(&ymd(2019, 5, 1)));
+        assert!(!holidays.contains(&ymd(2019, 12, 23)));
+        assert!(!holidays.contains(&ymd(2019, 2, 23)));
+    }
+
+    #[test]
+    fn calendar() {
+        let rules = Rules::default_rules().unwrap();
+        let calendar = collect_from_csv(
+            Cursor::new("10/4,法定休日\n10/10,10/11\n\"10/12\",出勤日\n2027/10/4,所定休日\n"),
+            &rules,
+            &mut Diagnostics::new(),
+        );
+        assert_eq!(calendar.offs.len(), 5);
+        assert!(matches!(
+            calendar.date_kind(&ymd(2027, 10, 4)),
+            DateKind::CompanyOff
+        ));
+
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 4)),
+            DateKind::StatutoryOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 10)),
+            DateKind::CompanyOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 12)),
+            DateKind::On
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 11, 3)),
+            DateKind::CompanyOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 5)),
+            DateKind::On
+        ));
+    }
+
+    #[test]
+    fn weekly_offs() {
+        let rules = include_str!("../設定.toml")
+            .replace(
+                "statutory_offs = []\ncompany_offs = []",
+                "statutory_offs = [\"日\"]\ncompany_offs = [\"土\"]",
+            )
+            .parse::<Rules>()
+            .unwrap();
+        let calendar = collect_from_csv(
+            Cursor::new("10/4,出勤日\n10/24,法定休日\n"),
+            &rules,
+            &mut Diagnostics::new(),
+        );
+
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 11)),
+            DateKind::StatutoryOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 17)),
+            DateKind::CompanyOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 4)),
+            DateKind::On
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 24)),
+            DateKind::StatutoryOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 12)),
+            DateKind::CompanyOff
+        ));
+        assert!(matches!(
+            calendar.date_kind(&ymd(2026, 10, 13)),
+            DateKind::On
+        ));
+    }
+}
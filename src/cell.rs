@@ -1,10 +1,10 @@
-use std::default::Default;
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Cell<T: Clone> {
     Data(T),
+    #[default]
     NoData,
 }
 
@@ -45,6 +45,19 @@ impl<T: Clone> Cell<T> {
     }
 }
 
+impl<T: FromStr + Clone> Cell<T> {
+    // 空欄なら NoData、読めない値ならエラーにする（FromStr と違い不正な値を見逃さない）
+    pub fn from_field(s: &str) -> anyhow::Result<Self> {
+        if s.trim().is_empty() {
+            return Ok(Self::NoData);
+        }
+        s.trim()
+            .parse::<T>()
+            .map(Self::Data)
+            .map_err(|_| anyhow!("Invalid value: {}", s))
+    }
+}
+
 impl<T: PartialEq + Clone> PartialEq for Cell<T> {
     fn eq(&self, other: &Self) -> bool {
         match self {
@@ -76,10 +89,3 @@ impl<T: FromStr + Clone> FromStr for Cell<T> {
         }
     }
 }
-
-impl<T: Clone> Default for Cell<T> {
-    fn default() -> Self {
-        Self::NoData
-    }
-}
-
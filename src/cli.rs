@@ -0,0 +1,213 @@
+use improve_pca_salary_x::{calendar, member, record, total};
+use std::path::{Path, PathBuf};
+
+pub const RULES_FILE_NAME: &str = "設定.toml";
+pub const ROUNDED_RECORDS_FILE_NAME: &str = "出勤簿_補正版.csv";
+pub const ROUNDED_DAILY_FILE_NAME: &str = "派遣日報.csv";
+pub const ROUNDED_TOTALS_FILE_NAME: &str = "PCA給与X_補正版.csv";
+pub const AUDIT_FILE_NAME: &str = "補正内訳.csv";
+pub const RECONCILIATION_FILE_NAME: &str = "照合.csv";
+pub const DIAGNOSTICS_FILE_NAME: &str = "診断.csv";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Convert,
+    Validate,
+    Explain,
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    pub rules: PathBuf,
+    pub roster: PathBuf,
+    pub records: PathBuf,
+    pub totals: PathBuf,
+    pub offs: PathBuf,
+    pub rounded_records: PathBuf,
+    pub rounded_daily: PathBuf,
+    pub rounded_totals: PathBuf,
+    pub audit: PathBuf,
+    pub reconciliation: PathBuf,
+    pub diagnostics: PathBuf,
+    pub strict: bool,
+    pub member: Option<u16>,
+    pub date: Option<String>,
+}
+
+impl Options {
+    // 引数が無ければ、dir にある入力ファイルを変換して dir に書き出す（ダブルクリックで起動した場合）
+    pub fn parse<I: IntoIterator<Item = String>>(args: I, dir: &Path) -> anyhow::Result<Self> {
+        let mut args = args.into_iter().peekable();
+        let command = match args.peek().map(|a| a.as_str()) {
+            Some("convert") => Some(Command::Convert),
+            Some("validate") => Some(Command::Validate),
+            Some("explain") => Some(Command::Explain),
+            Some("help") => Some(Command::Help),
+            Some(a) if !a.starts_with("-") => return Err(anyhow!("Unknown command: {}", a)),
+            _ => None,
+        };
+        // コマンドを省略した場合は convert
+        let command = match command {
+            Some(c) => {
+                args.next();
+                c
+            }
+            None => Command::Convert,
+        };
+
+        let mut paths: Vec<(String, PathBuf)> = vec![];
+        let mut strict = false;
+        let mut member = None;
+        let mut date = None;
+        let mut help = command == Command::Help;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(anyhow!("Missing value for {}", name));
+            match arg.as_str() {
+                "-h" | "--help" => help = true,
+                "--strict" => strict = true,
+                "--member" => {
+                    let v = value(&arg)?;
+                    member = Some(v.parse().map_err(|_| anyhow!("Invalid member id: {}", v))?);
+                }
+                "--date" => date = Some(value(&arg)?),
+                "--dir"
+                | "--out-dir"
+                | "--rules"
+                | "--roster"
+                | "--records"
+                | "--totals"
+                | "--offs"
+                | "--out-records"
+                | "--out-daily"
+                | "--out-totals"
+                | "--out-audit"
+                | "--out-reconciliation"
+                | "--out-diagnostics" => {
+                    let v = value(&arg)?;
+                    paths.push((arg, PathBuf::from(v)));
+                }
+                _ => return Err(anyhow!("Unknown option: {}", arg)),
+            }
+        }
+
+        let path = |name: &str| {
+            paths
+                .iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .map(|(_, p)| p.clone())
+        };
+        let dir = path("--dir").unwrap_or(dir.to_path_buf());
+        let out_dir = path("--out-dir").unwrap_or(dir.clone());
+        let input = |name: &str, file_name: &str| path(name).unwrap_or(dir.join(file_name));
+        let output = |name: &str, file_name: &str| path(name).unwrap_or(out_dir.join(file_name));
+
+        let options = Self {
+            command: match help {
+                true => Command::Help,
+                false => command,
+            },
+            rules: input("--rules", RULES_FILE_NAME),
+            roster: input("--roster", member::FILE_NAME),
+            records: input("--records", record::FILE_NAME),
+            totals: input("--totals", total::FILE_NAME),
+            offs: input("--offs", calendar::FILE_NAME),
+            rounded_records: output("--out-records", ROUNDED_RECORDS_FILE_NAME),
+            rounded_daily: output("--out-daily", ROUNDED_DAILY_FILE_NAME),
+            rounded_totals: output("--out-totals", ROUNDED_TOTALS_FILE_NAME),
+            audit: output("--out-audit", AUDIT_FILE_NAME),
+            reconciliation: output("--out-reconciliation", RECONCILIATION_FILE_NAME),
+            diagnostics: output("--out-diagnostics", DIAGNOSTICS_FILE_NAME),
+            strict,
+            member,
+            date,
+        };
+        if options.command == Command::Explain && options.member.is_none() {
+            return Err(anyhow!("explain requires --member"));
+        }
+        Ok(options)
+    }
+}
+
+pub fn usage() -> &'static str {
+    "使い方：improve-pca-salary-x [コマンド] [オプション]
+
+コマンド：
+  convert    入力を変換して書き出す（既定）
+  validate   入力を読み込んで確認し、診断.csv だけを書き出す
+  explain    社員（と日付）を指定して、計算の内訳を表示する
+  help       この説明を表示する
+
+オプション：
+  --dir <DIR>               入力ファイルのディレクトリ（既定：実行ファイルのディレクトリ）
+  --rules <FILE>            設定.toml
+  --roster <FILE>           名簿.csv
+  --records <FILE>          出勤簿.csv
+  --totals <FILE>           PCA給与X.csv
+  --offs <FILE>             休日.csv
+  --out-dir <DIR>           出力先のディレクトリ（既定：入力ファイルのディレクトリ）
+  --out-records <FILE>      出勤簿_補正版.csv
+  --out-daily <FILE>        派遣日報.csv
+  --out-totals <FILE>       PCA給与X_補正版.csv
+  --out-audit <FILE>        補正内訳.csv
+  --out-reconciliation <FILE>  照合.csv
+  --out-diagnostics <FILE>  診断.csv
+  --strict                  読み込めない行が1行でもあればエラーにする
+  --member <社員番号>       explain の対象の社員
+  --date <MM/DD>            explain の対象の日付（省略するとその月の全日）
+
+終了コード：0 成功、1 失敗（読み込めない行・要確認の行があった場合の validate、--strict を含む）、2 引数の誤り"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Command, Options};
+    use std::path::{Path, PathBuf};
+
+    fn parse(args: &[&str]) -> anyhow::Result<Options> {
+        Options::parse(args.iter().map(|a| a.to_string()), Path::new("/exe"))
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.command, Command::Convert);
+        assert_eq!(options.roster, PathBuf::from("/exe/名簿.csv"));
+        assert_eq!(
+            options.rounded_totals,
+            PathBuf::from("/exe/PCA給与X_補正版.csv")
+        );
+
+        let options = parse(&[
+            "validate",
+            "--dir",
+            "/in",
+            "--out-dir",
+            "/out",
+            "--records",
+            "/tmp/timecard.csv",
+            "--strict",
+        ])
+        .unwrap();
+        assert_eq!(options.command, Command::Validate);
+        assert_eq!(options.rules, PathBuf::from("/in/設定.toml"));
+        assert_eq!(options.records, PathBuf::from("/tmp/timecard.csv"));
+        assert_eq!(options.diagnostics, PathBuf::from("/out/診断.csv"));
+        assert!(options.strict);
+
+        let options = parse(&["--strict"]).unwrap();
+        assert_eq!(options.command, Command::Convert);
+
+        let options = parse(&["explain", "--member", "1", "--date", "10/01"]).unwrap();
+        assert_eq!(options.member, Some(1));
+        assert_eq!(options.date, Some("10/01".to_string()));
+
+        assert_eq!(parse(&["convert", "-h"]).unwrap().command, Command::Help);
+        assert!(parse(&["explain"]).is_err());
+        assert!(parse(&["export"]).is_err());
+        assert!(parse(&["--roster"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
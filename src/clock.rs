@@ -1,3 +1,6 @@
+use crate::calendar::Calendar;
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::cmp::Ordering;
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
@@ -8,8 +11,34 @@ pub struct Month {
 }
 
 impl Month {
-    pub fn new(year: u16, month: u16) -> Self {
-        Self { year, month }
+    // 締め期間の初日と末日。closing_day が0なら末日締め（その月の1日〜末日）、
+    // それ以外は前月の締め日の翌日からその月の締め日まで（25日締めなら12/26〜1/25）
+    pub fn period(&self, closing_day: u8) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+        let (year, month) = (self.year as i32, self.month as u32);
+        let (prev_year, prev_month) = match month {
+            1 => (year - 1, 12),
+            _ => (year, month - 1),
+        };
+        let (next_year, next_month) = match month {
+            12 => (year + 1, 1),
+            _ => (year, month + 1),
+        };
+        let invalid = || anyhow!("Invalid month: {}", self);
+        let period = match closing_day {
+            0 => (
+                NaiveDate::from_ymd_opt(year, month, 1),
+                NaiveDate::from_ymd_opt(next_year, next_month, 1).and_then(|d| d.pred_opt()),
+            ),
+            _ => (
+                NaiveDate::from_ymd_opt(prev_year, prev_month, closing_day as u32)
+                    .and_then(|d| d.succ_opt()),
+                NaiveDate::from_ymd_opt(year, month, closing_day as u32),
+            ),
+        };
+        match period {
+            (Some(start), Some(end)) => Ok((start, end)),
+            _ => Err(invalid()),
+        }
     }
 }
 
@@ -32,7 +61,7 @@ impl FromStr for Month {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DayKind {
     Sun,
     Mon,
@@ -44,6 +73,35 @@ pub enum DayKind {
     Unknown,
 }
 
+impl DayKind {
+    pub fn weekday(&self) -> Option<Weekday> {
+        match self {
+            DayKind::Sun => Some(Weekday::Sun),
+            DayKind::Mon => Some(Weekday::Mon),
+            DayKind::Tue => Some(Weekday::Tue),
+            DayKind::Wed => Some(Weekday::Wed),
+            DayKind::Thu => Some(Weekday::Thu),
+            DayKind::Fri => Some(Weekday::Fri),
+            DayKind::Sat => Some(Weekday::Sat),
+            DayKind::Unknown => None,
+        }
+    }
+}
+
+impl From<Weekday> for DayKind {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Sun => DayKind::Sun,
+            Weekday::Mon => DayKind::Mon,
+            Weekday::Tue => DayKind::Tue,
+            Weekday::Wed => DayKind::Wed,
+            Weekday::Thu => DayKind::Thu,
+            Weekday::Fri => DayKind::Fri,
+            Weekday::Sat => DayKind::Sat,
+        }
+    }
+}
+
 impl Display for DayKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match self {
@@ -82,7 +140,8 @@ impl FromStr for DayKind {
 #[derive(Debug, Clone)]
 pub enum DateKind {
     On,
-    Off,
+    CompanyOff,
+    StatutoryOff,
     Unknown,
 }
 
@@ -90,63 +149,127 @@ impl Display for DateKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match self {
             DateKind::On => "平日",
-            DateKind::Off => "休日",
+            DateKind::CompanyOff => "所定休日",
+            DateKind::StatutoryOff => "法定休日",
             DateKind::Unknown => "不明",
         };
         write!(f, "{}", s)
     }
 }
 
+impl FromStr for DateKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "平日" | "出勤日" => Ok(DateKind::On),
+            "休日" | "所定" | "所定休日" => Ok(DateKind::CompanyOff),
+            "法定" | "法定休日" => Ok(DateKind::StatutoryOff),
+            _ => Err(anyhow!("Unknown date kind: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RawDate {
+    pub year: Option<u16>,
     pub month: u8,
     pub date: u8,
 }
 
 impl RawDate {
-    pub fn new(month: u8, date: u8) -> Self {
-        Self { month, date }
+    pub fn new(year: Option<u16>, month: u8, date: u8) -> Self {
+        Self { year, month, date }
+    }
+
+    pub fn matches(&self, date: &NaiveDate) -> bool {
+        self.year.is_none_or(|y| y as i32 == date.year())
+            && self.month as u32 == date.month()
+            && self.date as u32 == date.day()
+    }
+
+    // 年が無い場合は、年月に最も近い日付とする（12/26〜1/25の締めなど）
+    pub fn resolve(&self, month: &Month) -> anyhow::Result<NaiveDate> {
+        if let Some(year) = self.year {
+            return NaiveDate::from_ymd_opt(year as i32, self.month as u32, self.date as u32)
+                .ok_or(anyhow!("Invalid date: {}", self));
+        }
+
+        let middle = NaiveDate::from_ymd_opt(month.year as i32, month.month as u32, 15)
+            .ok_or(anyhow!("Invalid month: {}", month))?;
+        [
+            month.year as i32 - 1,
+            month.year as i32,
+            month.year as i32 + 1,
+        ]
+        .iter()
+        .filter_map(|year| NaiveDate::from_ymd_opt(*year, self.month as u32, self.date as u32))
+        .min_by_key(|d| (*d - middle).num_days().abs())
+        .ok_or(anyhow!("Invalid date: {}", self))
     }
 }
 
 impl PartialEq for RawDate {
     fn eq(&self, other: &Self) -> bool {
-        self.month == other.month && self.date == other.date
+        let same_year = match (self.year, other.year) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        same_year && self.month == other.month && self.date == other.date
     }
 }
 
 impl Display for RawDate {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{: >02}/{: >02}", self.month, self.date)
+        match self.year {
+            Some(year) => write!(f, "{: >04}/{: >02}/{: >02}", year, self.month, self.date),
+            None => write!(f, "{: >02}/{: >02}", self.month, self.date),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Date {
     pub raw_date: RawDate,
+    pub calendar_date: Option<NaiveDate>,
     pub date_type: DateKind,
 }
 
 impl Date {
-    pub fn new(month: u8, date: u8) -> Self {
-        Self {
-            raw_date: RawDate::new(month, date),
-            date_type: DateKind::Unknown,
-        }
+    pub fn resolve(mut self, month: &Month) -> Self {
+        self.calendar_date = self.raw_date.resolve(month).ok();
+        self
     }
 
-    pub fn annotate(mut self, off_list: &[Date]) -> Self {
-        self.date_type = match off_list.iter().find(|o| o.raw_date == self.raw_date) {
-            Some(_) => DateKind::Off,
-            None => DateKind::On,
-        };
+    pub fn annotate(mut self, calendar: &Calendar) -> Self {
+        if let Some(date) = self.calendar_date {
+            self.date_type = calendar.date_kind(&date);
+        }
         self
     }
+
+    pub fn day_kind(&self) -> DayKind {
+        self.calendar_date
+            .map(|d| DayKind::from(d.weekday()))
+            .unwrap_or(DayKind::Unknown)
+    }
 }
 
 impl PartialEq for Date {
     fn eq(&self, other: &Self) -> bool {
-        self.raw_date == other.raw_date
+        match (self.calendar_date, other.calendar_date) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.raw_date == other.raw_date,
+        }
+    }
+}
+
+impl PartialOrd for Date {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.calendar_date, other.calendar_date) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
     }
 }
 
@@ -154,12 +277,20 @@ impl FromStr for Date {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut elements = s.split("/");
-        let month = elements.next().ok_or(anyhow!("Invalid format"))?;
-        let date = elements.next().ok_or(anyhow!("Invalid format"))?;
+        let elements: Vec<&str> = s.split("/").collect();
+        let raw_date = match elements.len() {
+            2 => RawDate::new(None, elements[0].parse()?, elements[1].parse()?),
+            3 => RawDate::new(
+                Some(elements[0].parse()?),
+                elements[1].parse()?,
+                elements[2].parse()?,
+            ),
+            _ => return Err(anyhow!("Invalid format")),
+        };
 
         Ok(Self {
-            raw_date: RawDate::new(month.parse()?, date.parse()?),
+            raw_date,
+            calendar_date: None,
             date_type: DateKind::Unknown,
         })
     }
@@ -167,11 +298,11 @@ impl FromStr for Date {
 
 impl Display for Date {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.raw_date.to_string())
+        write!(f, "{}", self.raw_date)
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Time {
     pub hours: u16,
     pub minutes: u16,
@@ -197,9 +328,19 @@ impl Time {
         .carry()
     }
 
-    pub fn round_up(&self) -> Self {
-        let minutes = (self.minutes as f32 / 15.).ceil() as u16 * 15;
-        Self::new(self.hours, minutes)
+    // 符号付きの分から作る（補正内訳で増減を表すため）
+    pub fn from_signed_minutes(minutes: i32) -> Self {
+        match minutes < 0 {
+            true => Self::new_as_negative(0, (-minutes) as u16),
+            false => Self::new(0, minutes as u16),
+        }
+    }
+
+    pub fn as_signed_minutes(&self) -> i32 {
+        match self.negative {
+            true => -(self.as_minutes() as i32),
+            false => self.as_minutes() as i32,
+        }
     }
 
     pub fn round_down(&self) -> Self {
@@ -213,6 +354,7 @@ impl Time {
         self.carry()
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn sub(self, other: &Self) -> Self {
         let self_as_minutes = self.as_minutes();
         let other_as_minutes = other.as_minutes();
@@ -241,8 +383,8 @@ impl Time {
 
     fn carry(mut self) -> Self {
         if self.minutes >= 60 {
-            self.minutes = self.minutes - 60;
-            self.hours = self.hours + 1;
+            self.minutes -= 60;
+            self.hours += 1;
         }
 
         if self.minutes >= 60 {
@@ -276,7 +418,7 @@ impl FromStr for Time {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Clock {
     pub hours: u16,
     pub minutes: u16,
@@ -289,12 +431,38 @@ impl Clock {
 
     pub fn round_up(&self) -> Self {
         let minutes = (self.minutes as f32 / 15.).ceil() as u16 * 15;
-        Self::new(self.hours, minutes)
+        Self::from_minutes(60 * self.hours + minutes)
     }
 
     pub fn round_down(&self) -> Self {
         let minutes = (self.minutes as f32 / 15.).floor() as u16 * 15;
-        Self::new(self.hours, minutes)
+        Self::from_minutes(60 * self.hours + minutes)
+    }
+
+    // 翌日の時刻は24時以降（25:30など）として扱う
+    pub fn next_day(&self) -> Self {
+        Self::from_minutes(self.as_minutes() + 60 * 24)
+    }
+
+    // 「翌1:30」や「25:30」「24:30」のような翌日の表記を、24時以降の時刻として読む
+    pub fn parse_extended(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        let explicit = s.starts_with("翌");
+        let s = s.trim_start_matches("翌").trim();
+        let over_midnight = s
+            .split(":")
+            .next()
+            .and_then(|h| h.parse::<u16>().ok())
+            .is_some_and(|h| h >= 24);
+        let clock: Self = s.parse()?;
+        match (explicit || over_midnight) && !clock.is_next_day() {
+            true => Ok(clock.next_day()),
+            false => Ok(clock),
+        }
+    }
+
+    pub fn is_next_day(&self) -> bool {
+        self.hours >= 24
     }
 
     pub fn diff(&self, other: &Self) -> Time {
@@ -320,10 +488,17 @@ impl Clock {
         60 * self.hours + self.minutes
     }
 
+    pub fn from_minutes(minutes: u16) -> Self {
+        Self {
+            hours: minutes / 60,
+            minutes: minutes % 60,
+        }
+    }
+
     fn carry(mut self) -> Self {
         if self.minutes >= 60 {
-            self.minutes = self.minutes - 60;
-            self.hours = self.hours + 1;
+            self.minutes -= 60;
+            self.hours += 1;
         }
 
         if self.hours == 24 {
@@ -357,10 +532,10 @@ impl FromStr for Clock {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Range {
-    start: Clock,
-    end: Clock,
+    pub start: Clock,
+    pub end: Clock,
 }
 
 impl Range {
@@ -375,11 +550,64 @@ impl Range {
     pub fn abs(&self) -> Time {
         self.end.diff(&self.start)
     }
+
+    pub fn next_day(&self) -> Self {
+        Self::new(self.start.next_day(), self.end.next_day())
+    }
+
+    pub fn tail(&self, length: &Time) -> Self {
+        let minutes = length.as_minutes().min(self.abs().as_minutes());
+        Self::new(
+            Clock::from_minutes(self.end.as_minutes() - minutes),
+            self.end.clone(),
+        )
+    }
+
+    pub fn overlap(&self, other: &Self) -> Time {
+        let start = self.start.as_minutes().max(other.start.as_minutes());
+        let end = self.end.as_minutes().min(other.end.as_minutes());
+
+        match end > start {
+            true => Time::new(0, end - start),
+            false => Time::new(0, 0),
+        }
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for Range {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut elements = s.split("-");
+        let start: Clock = elements
+            .next()
+            .ok_or(anyhow!("Invalid format"))?
+            .trim()
+            .parse()?;
+        let end: Clock = elements
+            .next()
+            .ok_or(anyhow!("Invalid format"))?
+            .trim()
+            .parse()?;
+
+        if !end.later_than(&start) {
+            return Err(anyhow!("Range must end after it starts: {}", s));
+        }
+
+        Ok(Self::new(start, end))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::clock::{Clock, Time};
+    use crate::clock::{Clock, Date, Month, Range, Time};
+    use chrono::NaiveDate;
 
     #[test]
     fn parse() {
@@ -388,6 +616,83 @@ mod tests {
 
         let time: Time = "9:4".parse().unwrap();
         assert_eq!(time, Time::new(9, 4));
+
+        let range: Range = "12:10-13:00".parse().unwrap();
+        assert_eq!(range, Range::new(Clock::new(12, 10), Clock::new(13, 0)));
+
+        assert!("13:00-12:10".parse::<Range>().is_err());
+        assert!("12:10".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn date() {
+        let month: Month = "2027/01".parse().unwrap();
+
+        let date = "12/26".parse::<Date>().unwrap().resolve(&month);
+        assert_eq!(date.calendar_date, NaiveDate::from_ymd_opt(2026, 12, 26));
+        let date = "1/25".parse::<Date>().unwrap().resolve(&month);
+        assert_eq!(date.calendar_date, NaiveDate::from_ymd_opt(2027, 1, 25));
+        let date = "2028/1/1".parse::<Date>().unwrap().resolve(&month);
+        assert_eq!(date.calendar_date, NaiveDate::from_ymd_opt(2028, 1, 1));
+        assert_eq!(date.to_string(), "2028/01/01");
+
+        let before = "12/31".parse::<Date>().unwrap().resolve(&month);
+        let after = "1/1".parse::<Date>().unwrap().resolve(&month);
+        assert!(before < after);
+
+        let date = "2/30".parse::<Date>().unwrap().resolve(&month);
+        assert_eq!(date.calendar_date, None);
+        assert!("12".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn overlap() {
+        let range: Range = "21:00-23:30".parse().unwrap();
+        let night: Range = "22:00-23:59".parse().unwrap();
+        assert_eq!(range.overlap(&night), Time::new(1, 30));
+        assert_eq!(night.overlap(&range), Time::new(1, 30));
+
+        let range: Range = "9:00-17:00".parse().unwrap();
+        assert_eq!(range.overlap(&night), Time::new(0, 0));
+    }
+
+    #[test]
+    fn period() {
+        let month: Month = "2026/01".parse().unwrap();
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            month.period(0).unwrap(),
+            (day(2026, 1, 1), day(2026, 1, 31))
+        );
+        assert_eq!(
+            month.period(25).unwrap(),
+            (day(2025, 12, 26), day(2026, 1, 25))
+        );
+        let month: Month = "2026/03".parse().unwrap();
+        assert_eq!(
+            month.period(28).unwrap(),
+            (day(2026, 3, 1), day(2026, 3, 28))
+        );
+    }
+
+    #[test]
+    fn next_day() {
+        let clock = Clock::new(6, 0).next_day();
+        assert_eq!(clock.hours, 30);
+        assert!(clock.is_next_day());
+        assert_eq!(clock.diff(&Clock::new(21, 0)), Time::new(9, 0));
+
+        let clock = Clock::new(0, 7).next_day();
+        assert_eq!(clock.round_down().hours, 24);
+        assert_eq!(Clock::new(23, 58).round_up().hours, 24);
+
+        let range = Range::new(Clock::new(21, 0), Clock::new(6, 0).next_day());
+        assert_eq!(
+            range.tail(&Time::new(1, 30)),
+            Range::new(Clock::new(4, 30).next_day(), Clock::new(6, 0).next_day())
+        );
+        assert!(range.includes(&"2:00-2:15".parse::<Range>().unwrap().next_day()));
+        assert!(!range.includes(&"2:00-2:15".parse::<Range>().unwrap()));
     }
 
     #[test]
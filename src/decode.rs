@@ -1,22 +1,123 @@
-use encoding_rs::SHIFT_JIS;
+use crate::diagnostics::Diagnostics;
+use crate::table::Row;
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_8};
 use std::fs::File;
 use std::io::Cursor;
 use std::io::Read;
+use std::str::{self, FromStr};
+
+// 入力の文字コード。Auto は BOM と UTF-8 として正しいかどうかで判定する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEncoding {
+    Auto,
+    ShiftJis,
+    Utf8,
+}
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace("-", "_").as_str() {
+            "auto" => Ok(InputEncoding::Auto),
+            "shift_jis" | "sjis" | "cp932" | "windows_31j" => Ok(InputEncoding::ShiftJis),
+            "utf8" | "utf_8" => Ok(InputEncoding::Utf8),
+            _ => Err(anyhow!("Unknown encoding: {}", s)),
+        }
+    }
+}
 
 pub trait Decode
 where
     Self: Sized + Read,
 {
-    fn decode(self) -> anyhow::Result<Cursor<String>>;
+    // 読み込んだ内容と、判定した（または指定された）文字コードを返す
+    fn decode(
+        self,
+        name: &str,
+        encoding: &InputEncoding,
+        diagnostics: &mut Diagnostics,
+    ) -> anyhow::Result<(Cursor<String>, &'static Encoding)>;
 }
 
 impl Decode for File {
-    fn decode(mut self) -> anyhow::Result<Cursor<String>> {
+    fn decode(
+        mut self,
+        name: &str,
+        encoding: &InputEncoding,
+        diagnostics: &mut Diagnostics,
+    ) -> anyhow::Result<(Cursor<String>, &'static Encoding)> {
         let mut buf = Vec::new();
         self.read_to_end(&mut buf)?;
 
-        let (decoded, _encoding, _errors) = SHIFT_JIS.decode(&buf);
-        Ok(Cursor::new(decoded.to_string()))
+        let (decoded, detected, had_errors) = decode_bytes(&buf, encoding);
+
+        // 変換できなかった文字は U+FFFD になるので、その行を警告する
+        if had_errors {
+            for (i, line) in decoded.lines().enumerate() {
+                if line.contains('\u{FFFD}') {
+                    let row = Row {
+                        line: i as u64 + 1,
+                        raw: line.to_string(),
+                        fields: vec![],
+                    };
+                    let reason = format!("{}として読めない文字があります", detected.name());
+                    diagnostics.warn(name, &row, reason);
+                }
+            }
+        }
+        Ok((Cursor::new(decoded), detected))
     }
 }
 
+// BOM があればそれに従い、無ければ UTF-8 として正しいかどうかで Shift_JIS（CP932）と区別する
+pub fn decode_bytes(buf: &[u8], encoding: &InputEncoding) -> (String, &'static Encoding, bool) {
+    let (encoding, bom_length) = match (encoding, Encoding::for_bom(buf)) {
+        (InputEncoding::Auto, Some(found)) => found,
+        (InputEncoding::Auto, None) => match str::from_utf8(buf) {
+            Ok(_) => (UTF_8, 0),
+            Err(_) => (SHIFT_JIS, 0),
+        },
+        (InputEncoding::Utf8, Some((e, length))) if e == UTF_8 => (UTF_8, length),
+        (InputEncoding::Utf8, _) => (UTF_8, 0),
+        (InputEncoding::ShiftJis, _) => (SHIFT_JIS, 0),
+    };
+
+    let (decoded, had_errors) = encoding.decode_without_bom_handling(&buf[bom_length..]);
+    (decoded.to_string(), encoding, had_errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::{decode_bytes, InputEncoding};
+    use encoding_rs::{SHIFT_JIS, UTF_8};
+
+    #[test]
+    fn detect() {
+        let text = "社員番号,氏名\n1,山田太郎\n";
+        let (sjis, _, _) = SHIFT_JIS.encode(text);
+
+        let (decoded, encoding, had_errors) = decode_bytes(&sjis, &InputEncoding::Auto);
+        assert_eq!(
+            (decoded.as_str(), encoding, had_errors),
+            (text, SHIFT_JIS, false)
+        );
+
+        let (decoded, encoding, _) = decode_bytes(text.as_bytes(), &InputEncoding::Auto);
+        assert_eq!((decoded.as_str(), encoding), (text, UTF_8));
+
+        let bom = [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat();
+        let (decoded, encoding, _) = decode_bytes(&bom, &InputEncoding::Auto);
+        assert_eq!((decoded.as_str(), encoding), (text, UTF_8));
+
+        // CP932 の拡張文字（①）
+        let (cp932, _, _) = SHIFT_JIS.encode("①");
+        assert_eq!(decode_bytes(&cp932, &InputEncoding::Auto).0, "①");
+
+        let (decoded, _, _) = decode_bytes(text.as_bytes(), &InputEncoding::ShiftJis);
+        assert_ne!(decoded, text);
+
+        let (_, _, had_errors) = decode_bytes(&sjis, &InputEncoding::Utf8);
+        assert!(had_errors);
+    }
+}
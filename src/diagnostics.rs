@@ -0,0 +1,131 @@
+use crate::table::Row;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Rejected,
+    Warning,
+    Review,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Severity::Rejected => "除外",
+            Severity::Warning => "警告",
+            Severity::Review => "要確認",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u64,
+    pub severity: Severity,
+    pub raw: String,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    pub fn export_to_csv(&self) -> Vec<String> {
+        vec![
+            self.file.clone(),
+            self.line.to_string(),
+            self.severity.to_string(),
+            self.raw.clone(),
+            self.reason.clone(),
+        ]
+    }
+}
+
+// 読み込めなかった行・気になる行の記録。診断.csvに書き出す
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reject(&mut self, file: &str, row: &Row, reason: &anyhow::Error) {
+        self.push(file, row, Severity::Rejected, format!("{:#}", reason));
+    }
+
+    pub fn warn(&mut self, file: &str, row: &Row, reason: String) {
+        self.push(file, row, Severity::Warning, reason);
+    }
+
+    // 読み込めたが、給与を確定する前に確認が必要な行
+    pub fn review(&mut self, file: &str, row: &Row, reason: String) {
+        self.push(file, row, Severity::Review, reason);
+    }
+
+    fn push(&mut self, file: &str, row: &Row, severity: Severity, reason: String) {
+        self.entries.push(Diagnostic {
+            file: file.to_string(),
+            line: row.line,
+            severity,
+            raw: row.raw.clone(),
+            reason,
+        });
+    }
+
+    pub fn rejected_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|d| d.severity == Severity::Rejected)
+            .count()
+    }
+
+    pub fn review_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|d| d.severity == Severity::Review)
+            .count()
+    }
+
+    // ファイルごとの件数をコンソールに表示する
+    pub fn print_summary(&self) {
+        if self.entries.is_empty() {
+            println!("読み込めなかった行はありません");
+            return;
+        }
+
+        let mut files: Vec<&str> = vec![];
+        for d in self.entries.iter() {
+            if !files.contains(&d.file.as_str()) {
+                files.push(&d.file);
+            }
+        }
+        for file in files {
+            let count = |severity: Severity| {
+                self.entries
+                    .iter()
+                    .filter(|d| d.file == file && d.severity == severity)
+                    .count()
+            };
+            println!(
+                "{}：除外{}行、警告{}行、要確認{}行",
+                file,
+                count(Severity::Rejected),
+                count(Severity::Warning),
+                count(Severity::Review)
+            );
+        }
+        for d in self
+            .entries
+            .iter()
+            .filter(|d| d.severity != Severity::Warning)
+        {
+            println!("  {}：{}の{}行目：{}", d.severity, d.file, d.line, d.reason);
+        }
+    }
+}
+
+pub fn get_csv_headings() -> &'static str {
+    "ファイル,行,区分,内容,理由"
+}
@@ -0,0 +1,237 @@
+use crate::diagnostics::Diagnostics;
+use crate::table::{to_line, Row};
+use anyhow::Context;
+use encoding_rs::SHIFT_JIS;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// 出力の文字コード
+// ShiftJis はJIS X 0208の範囲だけを許し、NEC・IBM拡張文字は警告する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputEncoding {
+    ShiftJis,
+    Cp932,
+    Utf8,
+    Utf8Bom,
+}
+
+impl Display for OutputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            OutputEncoding::ShiftJis => "Shift_JIS",
+            OutputEncoding::Cp932 => "CP932",
+            OutputEncoding::Utf8 => "UTF-8",
+            OutputEncoding::Utf8Bom => "UTF-8（BOM付き）",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for OutputEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace("-", "_").as_str() {
+            "shift_jis" | "sjis" => Ok(OutputEncoding::ShiftJis),
+            "cp932" | "windows_31j" => Ok(OutputEncoding::Cp932),
+            "utf8" | "utf_8" => Ok(OutputEncoding::Utf8),
+            "utf8_bom" | "utf_8_bom" | "utf_8_sig" => Ok(OutputEncoding::Utf8Bom),
+            _ => Err(anyhow!("Unknown encoding: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(anyhow!("Unknown line ending: {}", s)),
+        }
+    }
+}
+
+// name は診断.csvに書くファイル名
+pub struct CsvWriter<W: Write> {
+    name: String,
+    writer: W,
+    encoding: OutputEncoding,
+    line_ending: LineEnding,
+    line: u64,
+}
+
+impl CsvWriter<BufWriter<File>> {
+    pub fn create(
+        path: &Path,
+        encoding: &OutputEncoding,
+        line_ending: &LineEnding,
+    ) -> anyhow::Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map_or("".to_string(), |n| n.to_string_lossy().to_string());
+        Self::new(BufWriter::new(file), &name, encoding, line_ending)
+    }
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(
+        mut writer: W,
+        name: &str,
+        encoding: &OutputEncoding,
+        line_ending: &LineEnding,
+    ) -> anyhow::Result<Self> {
+        if *encoding == OutputEncoding::Utf8Bom {
+            writer.write_all(&[0xEF, 0xBB, 0xBF])?;
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            writer,
+            encoding: encoding.clone(),
+            line_ending: line_ending.clone(),
+            line: 0,
+        })
+    }
+
+    pub fn write_headings(&mut self, headings: &str) -> anyhow::Result<()> {
+        let fields: Vec<&str> = headings.split(",").collect();
+        self.write_row(&fields, &mut Diagnostics::new())
+    }
+
+    // 出力の文字コードで表せない文字があれば、その行を警告する
+    pub fn write_row<S: AsRef<str>>(
+        &mut self,
+        fields: &[S],
+        diagnostics: &mut Diagnostics,
+    ) -> anyhow::Result<()> {
+        let line = to_line(fields)?;
+        self.line += 1;
+
+        let unmappable = unmappable_chars(&line, &self.encoding);
+        if !unmappable.is_empty() {
+            let row = Row {
+                line: self.line,
+                raw: line.clone(),
+                fields: vec![],
+            };
+            let reason = format!(
+                "{}で表せない文字があります：{}",
+                self.encoding,
+                unmappable.iter().collect::<String>()
+            );
+            diagnostics.warn(&self.name, &row, reason);
+        }
+
+        let line = line + self.line_ending.as_str();
+        match self.encoding {
+            OutputEncoding::ShiftJis | OutputEncoding::Cp932 => {
+                let (encoded, _encoding, _errors) = SHIFT_JIS.encode(&line);
+                self.writer.write_all(&encoded)?;
+            }
+            OutputEncoding::Utf8 | OutputEncoding::Utf8Bom => {
+                self.writer.write_all(line.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+pub fn unmappable_chars(s: &str, encoding: &OutputEncoding) -> Vec<char> {
+    match encoding {
+        OutputEncoding::ShiftJis | OutputEncoding::Cp932 => {
+            let strict = *encoding == OutputEncoding::ShiftJis;
+            let (_, _, had_errors) = SHIFT_JIS.encode(s);
+            if !had_errors && !strict {
+                return vec![];
+            }
+            let mut found: Vec<char> = vec![];
+            for c in s.chars() {
+                let mut buf = [0; 4];
+                let (encoded, _, had_errors) = SHIFT_JIS.encode(c.encode_utf8(&mut buf));
+                let extended = strict && is_extended_lead_byte(encoded[0]);
+                if (had_errors || extended) && !found.contains(&c) {
+                    found.push(c);
+                }
+            }
+            found
+        }
+        OutputEncoding::Utf8 | OutputEncoding::Utf8Bom => vec![],
+    }
+}
+
+// CP932のNEC特殊文字（0x87）、NEC選定IBM拡張文字（0xED〜0xEE）、IBM拡張文字（0xFA〜0xFC）
+fn is_extended_lead_byte(b: u8) -> bool {
+    b == 0x87 || (0xED..=0xEE).contains(&b) || (0xFA..=0xFC).contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encode::{unmappable_chars, OutputEncoding};
+
+    #[test]
+    fn parse_output_encoding() {
+        assert_eq!(
+            "Shift-JIS".parse::<OutputEncoding>().unwrap(),
+            OutputEncoding::ShiftJis
+        );
+        assert_eq!(
+            "cp932".parse::<OutputEncoding>().unwrap(),
+            OutputEncoding::Cp932
+        );
+        assert_eq!(
+            "Windows-31J".parse::<OutputEncoding>().unwrap(),
+            OutputEncoding::Cp932
+        );
+    }
+
+    #[test]
+    fn unmappable() {
+        assert!(unmappable_chars("山田太郎,高崎", &OutputEncoding::ShiftJis).is_empty());
+        assert_eq!(
+            unmappable_chars("𠮷野家,𠮷田,😀", &OutputEncoding::ShiftJis),
+            vec!['𠮷', '😀']
+        );
+        // NEC・IBM拡張文字はShift_JISでは警告し、CP932では通す
+        assert_eq!(
+            unmappable_chars("髙﨑,①", &OutputEncoding::ShiftJis),
+            vec!['髙', '﨑', '①']
+        );
+        assert!(unmappable_chars("山田太郎,髙﨑,①", &OutputEncoding::Cp932).is_empty());
+        assert_eq!(
+            unmappable_chars("髙﨑,𠮷", &OutputEncoding::Cp932),
+            vec!['𠮷']
+        );
+        assert!(unmappable_chars("𠮷野家", &OutputEncoding::Utf8).is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+// テストで共通に使う入力。名簿と出勤簿は見出し行を除いた行だけを渡す
+use crate::total;
+use crate::{
+    compute_totals, load_calendar, load_records, load_roster, load_totals, Diagnostics, Record,
+    Rules, Total,
+};
+use std::io::Cursor;
+
+pub const ROSTER_HEADINGS: &str = "社員番号,氏名,区分,派遣元\n";
+pub const RECORDS_HEADINGS: &str =
+    "年月,社員番号,氏名,日付,曜日,出勤時刻,退勤時刻,休憩時間,労働時間,備考,出勤日数\n";
+
+// PCA給与Xの見出し行。補正版にだけある列は除く
+pub fn totals_headings() -> String {
+    let added = [
+        "氏名",
+        "補正出勤時間",
+        "出勤時刻差分",
+        "法定外労働時間",
+        "法定内残業時間",
+        "深夜労働時間",
+    ];
+    let headings: Vec<&str> = total::get_csv_headings()
+        .split(",")
+        .filter(|h| !added.contains(h))
+        .collect();
+    headings.join(",") + "\n"
+}
+
+pub struct Converted {
+    pub records: Vec<Record>,
+    pub totals: Vec<Total>,
+    pub diagnostics: Diagnostics,
+}
+
+// 読み込みから集計まで。totals は見出し行を含めたPCA給与Xの内容
+pub fn convert(rules: &Rules, roster: &str, offs: &str, records: &str, totals: &str) -> Converted {
+    let mut diagnostics = Diagnostics::new();
+    let roster = load_roster(
+        Cursor::new(ROSTER_HEADINGS.to_string() + roster),
+        rules,
+        &mut diagnostics,
+    );
+    let calendar = load_calendar(Cursor::new(offs.to_string()), rules, &mut diagnostics);
+    let records = load_records(
+        Cursor::new(RECORDS_HEADINGS.to_string() + records),
+        &roster,
+        &calendar,
+        rules,
+        &mut diagnostics,
+    )
+    .unwrap();
+    let totals = load_totals(
+        Cursor::new(totals.to_string()),
+        &roster,
+        rules,
+        &mut diagnostics,
+    )
+    .unwrap();
+    let totals = compute_totals(totals, &records, rules);
+
+    Converted {
+        records,
+        totals,
+        diagnostics,
+    }
+}
+
+// 既定の設定に所定労働時間5時間の区分 PartTimeE（ラベル "E"）を加える
+pub fn rules_with_part_time_e(start_at: &str, end_at: Option<&str>) -> Rules {
+    part_time_e_text(start_at, end_at).parse().unwrap()
+}
+
+pub fn part_time_e_text(start_at: &str, end_at: Option<&str>) -> String {
+    let mut text = include_str!("../設定.toml").to_string()
+        + "\n[kinds.PartTimeE]\n"
+        + &format!("start_at = \"{}\"\n", start_at)
+        + "lunch = \"12:10-13:00\"\n"
+        + "force_breaks = []\n"
+        + "nominal_work_time = \"5:00\"\n";
+    if let Some(end_at) = end_at {
+        text += &format!("end_at = \"{}\"\n", end_at);
+    }
+    text.replace(
+        "\"D\" = \"PartTimeD\"",
+        "\"D\" = \"PartTimeD\"\n\"E\" = \"PartTimeE\"",
+    )
+}
@@ -0,0 +1,191 @@
+//! PCA給与Xの勤怠データを、出勤簿から集計し直して補正するライブラリ。
+//!
+//! 読み込み（`load_*`）→ 集計（`compute_totals`）→ 書き出し（`export_*`）の順に使う。
+//! 読み込む内容は文字コード変換済みの文字列で渡す（ファイルからは `decode::Decode` を使う）。
+
+#[macro_use]
+extern crate anyhow;
+
+pub mod calendar;
+pub mod cell;
+pub mod clock;
+pub mod decode;
+pub mod diagnostics;
+pub mod encode;
+#[cfg(test)]
+mod fixtures;
+pub mod member;
+pub mod reconcile;
+pub mod record;
+pub mod rules;
+pub mod table;
+pub mod total;
+
+pub use crate::calendar::Calendar;
+pub use crate::clock::{Clock, Date, DateKind, Time};
+pub use crate::diagnostics::Diagnostics;
+pub use crate::encode::CsvWriter;
+pub use crate::member::Member;
+pub use crate::reconcile::Reconciliation;
+pub use crate::record::{Breakdown, Record};
+pub use crate::rules::Rules;
+pub use crate::total::Total;
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+pub fn load_roster<R: Read>(
+    reader: R,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> HashSet<Member> {
+    member::collect_from_csv(reader, rules, diagnostics)
+}
+
+pub fn load_calendar<R: Read>(reader: R, rules: &Rules, diagnostics: &mut Diagnostics) -> Calendar {
+    calendar::collect_from_csv(reader, rules, diagnostics)
+}
+
+pub fn load_records<R: Read>(
+    reader: R,
+    roster: &HashSet<Member>,
+    calendar: &Calendar,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Vec<Record>> {
+    record::collect_from_csv(reader, roster, calendar, rules, diagnostics)
+}
+
+pub fn load_totals<R: Read>(
+    reader: R,
+    roster: &HashSet<Member>,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Vec<Total>> {
+    total::collect_from_csv(reader, roster, rules, diagnostics)
+}
+
+// 社員ごとに出勤簿を集計して、PCA給与Xの値を補正する。集計できない社員は空の行になる
+pub fn compute_totals(totals: Vec<Total>, records: &[Record], rules: &Rules) -> Vec<Total> {
+    totals
+        .into_iter()
+        .map(|t| {
+            let the_records = records.iter().filter(|r| r.member == t.member).collect();
+            t.total(the_records, rules).unwrap_or(Total::empty())
+        })
+        .collect()
+}
+
+// 集計済みの totals と出勤簿を社員ごとに照合する
+pub fn reconcile(totals: &[Total], records: &[Record], rules: &Rules) -> Vec<Reconciliation> {
+    reconcile::reconcile(totals, records, rules)
+}
+
+pub fn export_records<W: Write>(
+    writer: &mut CsvWriter<W>,
+    records: &[Record],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_headings(record::get_csv_headings())?;
+    for r in records {
+        writer.write_row(&r.export_rounded_to_csv()?, diagnostics)?;
+    }
+    writer.flush()
+}
+
+pub fn export_daily<W: Write>(
+    writer: &mut CsvWriter<W>,
+    records: &[Record],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_headings(record::get_daily_csv_headings())?;
+    for r in records {
+        writer.write_row(&r.export_rounded_to_daily_csv(false)?, diagnostics)?;
+    }
+    writer.flush()
+}
+
+pub fn export_audit<W: Write>(
+    writer: &mut CsvWriter<W>,
+    records: &[Record],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_headings(record::get_audit_csv_headings())?;
+    for r in records {
+        writer.write_row(&r.export_audit_to_csv()?, diagnostics)?;
+    }
+    writer.flush()
+}
+
+pub fn export_totals<W: Write>(
+    writer: &mut CsvWriter<W>,
+    totals: &[Total],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_row(&total::csv_headings(totals), diagnostics)?;
+    for t in totals {
+        writer.write_row(&t.export_to_csv(), diagnostics)?;
+    }
+    writer.flush()
+}
+
+pub fn export_reconciliation<W: Write>(
+    writer: &mut CsvWriter<W>,
+    reconciliations: &[Reconciliation],
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_headings(reconcile::get_csv_headings())?;
+    for r in reconciliations {
+        writer.write_row(&r.export_to_csv(), diagnostics)?;
+    }
+    writer.flush()
+}
+
+pub fn export_diagnostics<W: Write>(
+    writer: &mut CsvWriter<W>,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<()> {
+    writer.write_headings(diagnostics::get_csv_headings())?;
+    for d in &diagnostics.entries {
+        writer.write_row(&d.export_to_csv(), &mut Diagnostics::new())?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encode::{LineEnding, OutputEncoding};
+    use crate::fixtures;
+    use crate::{export_totals, CsvWriter, Rules, Time};
+
+    #[test]
+    fn convert() {
+        let rules = Rules::default_rules().unwrap();
+        let mut converted = fixtures::convert(
+            &rules,
+            "1,山田太郎,LUC社員,\n",
+            "10/4,法定休日\n",
+            "2026/10,1,山田太郎,10/1,木,8:20,19:40,1:30,9:50,,1\n",
+            &(fixtures::totals_headings() + "1,20,160:00,1,9:50\n"),
+        );
+        assert!(converted.diagnostics.entries.is_empty());
+
+        let totals = converted.totals;
+        assert_eq!(
+            totals[0].rounded_work_time.peek().unwrap(),
+            &Time::new(8, 30)
+        );
+
+        let mut writer = CsvWriter::new(
+            vec![],
+            "PCA給与X_補正版.csv",
+            &OutputEncoding::Utf8,
+            &LineEnding::Crlf,
+        )
+        .unwrap();
+        export_totals(&mut writer, &totals, &mut converted.diagnostics).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(output.starts_with("社員コード,氏名,"));
+        assert!(output.contains("\r\n1,山田太郎,20,160.00,1,09.50,08.30,"));
+    }
+}
@@ -1,115 +1,316 @@
 #[macro_use]
 extern crate anyhow;
 
-mod cell;
-mod clock;
-mod decode;
-mod member;
-mod record;
-mod total;
-
-use crate::clock::Date;
-use crate::decode::Decode;
-use crate::total::Total;
-use encoding_rs::SHIFT_JIS;
+mod cli;
+
+use crate::cli::{Command, Options};
+use anyhow::Context;
+use improve_pca_salary_x::decode::Decode;
+use improve_pca_salary_x::reconcile::Status;
+use improve_pca_salary_x::{calendar, member, record, total};
+use improve_pca_salary_x::{CsvWriter, Date, DateKind, Diagnostics, Record, Rules, Total};
 use std::env;
-use std::fs::File;
-use std::io::{self, BufRead, BufWriter, Write};
+use std::fs::{self, File};
+use std::io::Cursor;
+use std::path::Path;
+use std::process;
 
-fn main() -> anyhow::Result<()> {
-    println!("起動しています...");
-    let dir_work = env::current_dir()?;
-    let exe = env::current_exe()?;
+fn main() {
+    let dir_work = env::current_dir().unwrap_or_default();
+    let exe = env::current_exe().unwrap_or_default();
     let dir = exe.parent().unwrap_or(dir_work.as_path());
-    println!("起動ディレクトリ：{:?}", dir);
 
-    let path_roster = dir.join("名簿.csv");
-    let path_records = dir.join("出勤簿.csv");
-    let path_totals = dir.join("PCA給与X.csv");
-    let path_offs = dir.join("休日.csv");
-    let path_rounded_records = dir.join("出勤簿_補正版.csv");
-    let path_rounded_daily = dir.join("派遣日報.csv");
-    let path_rounded_totals = dir.join("PCA給与X_補正版.csv");
+    let options = match Options::parse(env::args().skip(1), dir) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", cli::usage());
+            process::exit(2);
+        }
+    };
+
+    let result = match options.command {
+        Command::Convert => convert(&options),
+        Command::Validate => validate(&options),
+        Command::Explain => explain(&options),
+        Command::Help => {
+            println!("{}", cli::usage());
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        process::exit(1);
+    }
+}
+
+struct Inputs {
+    rules: Rules,
+    records: Vec<Record>,
+    totals: Vec<Total>,
+    diagnostics: Diagnostics,
+}
+
+fn load(options: &Options) -> anyhow::Result<Inputs> {
+    println!("起動しています...");
+    println!(
+        "入力ディレクトリ：{:?}",
+        options.roster.parent().unwrap_or(Path::new(""))
+    );
+
+    println!("設定を読み込んでいます...");
+    let mut rules = Rules::load(&options.rules)?;
+    rules.strict = rules.strict || options.strict;
+    println!("完了");
+
+    let mut diagnostics = Diagnostics::new();
 
     println!("名簿を読み込んでいます...");
-    let reader_roster = File::open(&path_roster)?.decode()?;
-    let roster = member::collect_from_csv(reader_roster);
+    let reader_roster = open(&options.roster, member::FILE_NAME, &rules, &mut diagnostics)?;
+    let roster = improve_pca_salary_x::load_roster(reader_roster, &rules, &mut diagnostics);
     println!("完了");
 
     println!("休日リストを読み込んでいます...");
-    let reader_offs = File::open(&path_offs)?.decode()?;
-    let offs: Vec<Date> = reader_offs
-        .lines()
-        .flat_map(|line| {
-            line.unwrap_or("".to_string())
-                .split(",")
-                .map(|s| s.parse::<Date>().unwrap_or(Date::new(0, 0)))
-                .collect::<Vec<Date>>()
-        })
-        .collect();
+    let reader_offs = open(&options.offs, calendar::FILE_NAME, &rules, &mut diagnostics)?;
+    let calendar = improve_pca_salary_x::load_calendar(reader_offs, &rules, &mut diagnostics);
     println!("完了");
 
     println!("出勤簿を読み込んでいます...");
-    let reader_records = File::open(&path_records)?.decode()?;
-    let records = record::collect_from_csv(reader_records, &roster, &offs);
+    let reader_records = open(
+        &options.records,
+        record::FILE_NAME,
+        &rules,
+        &mut diagnostics,
+    )?;
+    let records = improve_pca_salary_x::load_records(
+        reader_records,
+        &roster,
+        &calendar,
+        &rules,
+        &mut diagnostics,
+    )?;
     println!("完了");
 
     println!("PCA給与Xを読み込んでいます...");
-    let reader_totals = File::open(&path_totals)?.decode()?;
-    let totals = total::collect_from_csv(reader_totals, &roster);
+    let reader_totals = open(&options.totals, total::FILE_NAME, &rules, &mut diagnostics)?;
+    let totals =
+        improve_pca_salary_x::load_totals(reader_totals, &roster, &rules, &mut diagnostics)?;
     println!("完了");
 
+    println!("読み込み結果");
+    diagnostics.print_summary();
+
+    Ok(Inputs {
+        rules,
+        records,
+        totals,
+        diagnostics,
+    })
+}
+
+fn open(
+    path: &Path,
+    name: &str,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Cursor<String>> {
+    let (reader, encoding) = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .decode(name, &rules.input_encoding, diagnostics)?;
+    println!("文字コード：{}", encoding.name());
+    Ok(reader)
+}
+
+// 出力先のディレクトリが無ければ、入力を読み込む前に作る
+fn create_output_dirs(paths: &[&Path]) -> anyhow::Result<()> {
+    for dir in paths.iter().filter_map(|p| p.parent()) {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn convert(options: &Options) -> anyhow::Result<()> {
+    create_output_dirs(&[
+        &options.rounded_records,
+        &options.rounded_daily,
+        &options.rounded_totals,
+        &options.audit,
+        &options.reconciliation,
+        &options.diagnostics,
+    ])?;
+    let Inputs {
+        rules,
+        records,
+        totals,
+        mut diagnostics,
+    } = load(options)?;
+    if rules.strict && diagnostics.rejected_count() > 0 {
+        write_diagnostics(&options.diagnostics, &diagnostics, &rules)?;
+        return Err(anyhow!(
+            "{} input lines were rejected; see {}",
+            diagnostics.rejected_count(),
+            options.diagnostics.display()
+        ));
+    }
+
     println!("集計しています...");
-    let rounded_totals = totals.into_iter().map(|t| {
-        let the_records = records.iter().filter(|r| r.member == t.member).collect();
-        t.total(the_records).unwrap_or(Total::empty())
-    });
+    let rounded_totals = improve_pca_salary_x::compute_totals(totals, &records, &rules);
     println!("完了");
 
     println!("書き出しています...");
-    let mut target_records = io::BufWriter::new(File::create(&path_rounded_records)?);
-    let mut target_daily = io::BufWriter::new(File::create(&path_rounded_daily)?);
-    let mut target_totals = io::BufWriter::new(File::create(&path_rounded_totals)?);
+    let count = diagnostics.entries.len();
+    let create = |path: &Path| CsvWriter::create(path, &rules.output_encoding, &rules.line_ending);
+    improve_pca_salary_x::export_records(
+        &mut create(&options.rounded_records)?,
+        &records,
+        &mut diagnostics,
+    )?;
+    improve_pca_salary_x::export_daily(
+        &mut create(&options.rounded_daily)?,
+        &records,
+        &mut diagnostics,
+    )?;
+    improve_pca_salary_x::export_audit(&mut create(&options.audit)?, &records, &mut diagnostics)?;
+    improve_pca_salary_x::export_totals(
+        &mut create(&options.rounded_totals)?,
+        &rounded_totals,
+        &mut diagnostics,
+    )?;
+    let reconciliations = improve_pca_salary_x::reconcile(&rounded_totals, &records, &rules);
+    improve_pca_salary_x::export_reconciliation(
+        &mut create(&options.reconciliation)?,
+        &reconciliations,
+        &mut diagnostics,
+    )?;
+    println!("完了");
 
-    write_line_with_shift_jis(&mut target_records, record::get_csv_headings().to_string())?;
-    for r in &records {
-        write_line_with_shift_jis(&mut target_records, r.export_rounded_to_csv()?)?;
+    let count_of = |status: Status| {
+        reconciliations
+            .iter()
+            .filter(|r| r.status == status)
+            .count()
+    };
+    println!(
+        "照合結果：差あり{}人、PCA給与Xに無い{}人、出勤簿に無い{}人",
+        count_of(Status::Differs),
+        count_of(Status::MissingInTotals),
+        count_of(Status::MissingInRecords)
+    );
+    for d in diagnostics.entries.iter().skip(count) {
+        println!("警告：{}の{}行目：{}", d.file, d.line, d.reason);
     }
+    write_diagnostics(&options.diagnostics, &diagnostics, &rules)?;
 
-    write_line_with_shift_jis(
-        &mut target_daily,
-        record::get_daily_csv_headings().to_string(),
-    )?;
-    let mut pre: Option<Date> = None;
-    for r in &records {
-        write_line_with_shift_jis(
-            &mut target_daily,
-            r.export_rounded_to_daily_csv(
-                /*match pre {
-                    Some(ref date) => date != r.date.peek().unwrap(),
-                    None => true,
-                }*/
-                false,
-            )?,
-        )?;
-        pre = Some(r.date.peek()?.clone());
+    Ok(())
+}
+
+// 読み込みだけを行い、診断.csv を書き出す。読み込めない行があれば失敗とする
+fn validate(options: &Options) -> anyhow::Result<()> {
+    create_output_dirs(&[&options.diagnostics])?;
+    let inputs = load(options)?;
+    write_diagnostics(&options.diagnostics, &inputs.diagnostics, &inputs.rules)?;
+
+    let rejected = inputs.diagnostics.rejected_count();
+    let review = inputs.diagnostics.review_count();
+    match rejected + review {
+        0 => Ok(()),
+        _ => Err(anyhow!(
+            "{} input lines were rejected and {} need review; see {}",
+            rejected,
+            review,
+            options.diagnostics.display()
+        )),
     }
+}
+
+fn explain(options: &Options) -> anyhow::Result<()> {
+    let inputs = load(options)?;
+    let date = match &options.date {
+        Some(d) => Some(d.parse::<Date>()?),
+        None => None,
+    };
 
-    write_line_with_shift_jis(&mut target_totals, total::get_csv_headings().to_string())?;
-    for t in rounded_totals {
-        write_line_with_shift_jis(&mut target_totals, t.export_to_csv())?;
+    let the_records: Vec<&Record> = inputs
+        .records
+        .iter()
+        .filter(|r| r.member.peek().is_ok_and(|m| Some(m.id) == options.member))
+        .filter(|r| match (&date, r.calendar_date()) {
+            (Some(d), Ok(c)) => d.raw_date.matches(&c),
+            (Some(_), Err(_)) => false,
+            (None, _) => true,
+        })
+        .collect();
+    if the_records.is_empty() {
+        return Err(anyhow!("No records have been found"));
     }
-    println!("完了");
 
+    for r in the_records {
+        println!();
+        let member = r.member.peek()?;
+        // 年月が空欄などで日付区分が決まらない記録も、その記録だけ注記して続ける
+        let date_type = match r.date.peek() {
+            Ok(d) => d.date_type.clone(),
+            Err(_) => DateKind::Unknown,
+        };
+        println!(
+            "{}（{}）{} {} {}",
+            r.date, r.day, date_type, member.id, member.name
+        );
+        println!(
+            "  出勤 {} 退勤 {} 休憩 {}",
+            r.came_at, r.left_at, r.break_time
+        );
+        // 欠勤などで補正できない記録は、理由だけ表示して次へ進む
+        let breakdown = match r.breakdown() {
+            Ok(b) => b,
+            Err(_) if r.came_at.peek().is_err() || r.left_at.peek().is_err() => {
+                println!("  打刻なし");
+                continue;
+            }
+            Err(e) => {
+                println!("  補正できません：{:#}", e);
+                continue;
+            }
+        };
+        for line in breakdown.trace() {
+            println!("  {}", line);
+        }
+        let summary = || -> anyhow::Result<(String, String)> {
+            let times = format!(
+                "  補正労働時間 {} 法定外 {} 法定内残業 {} 通常 {} 深夜 {}",
+                r.rounded_work_time()?,
+                r.over_work_time()?,
+                r.within_over_work_time()?,
+                r.normal_work_time()?,
+                r.late_night_work_time()?
+            );
+            let tardiness = format!(
+                "  遅刻 {} 早退 {}（規定 {}-{}）",
+                r.late_time()?,
+                r.early_leave_time()?,
+                member.start_at(),
+                member.end_at()
+            );
+            Ok((times, tardiness))
+        };
+        match summary() {
+            Ok((times, tardiness)) => {
+                println!("{}", times);
+                println!("{}", tardiness);
+            }
+            Err(_) if matches!(date_type, DateKind::Unknown) => {
+                println!("  日付区分が分からないので集計できません（年月・日付を確認してください）")
+            }
+            Err(e) => println!("  集計できません：{:#}", e),
+        }
+    }
     Ok(())
 }
 
-fn write_line_with_shift_jis(
-    writer: &mut BufWriter<File>,
-    s: String,
-) -> Result<(), std::io::Error> {
-    let line = s + "\n";
-    let (encoded, _encoding, _res) = SHIFT_JIS.encode(&line);
-    writer.write(&encoded)?;
-    Ok(())
+fn write_diagnostics(path: &Path, diagnostics: &Diagnostics, rules: &Rules) -> anyhow::Result<()> {
+    let mut target = CsvWriter::create(path, &rules.output_encoding, &rules.line_ending)?;
+    improve_pca_salary_x::export_diagnostics(&mut target, diagnostics)
 }
@@ -1,68 +1,27 @@
-use crate::clock::{Clock, Range};
+use crate::cell::Cell;
+use crate::clock::{Clock, Range, Time};
+use crate::diagnostics::Diagnostics;
+use crate::rules::{Rules, Schedule};
+use crate::table::read_rows;
+use anyhow::Context;
 use std::collections::HashSet;
 use std::fmt::{self, Display};
-use std::io::BufRead;
-use std::str::FromStr;
+use std::io::Read;
+
+pub const FILE_NAME: &str = "名簿.csv";
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub enum MemberKind {
-    FullTime,
-    Associate,
-    PartTimeA,
-    PartTimeB,
-    PartTimeC,
-    PartTimeD,
-    Unknown,
-}
+pub struct MemberKind(pub String);
 
 impl MemberKind {
-    pub fn force_breaks(&self) -> Vec<Range> {
-        let break0 = Range::new(Clock::new(10, 30), Clock::new(10, 40));
-        let break1 = Range::new(Clock::new(15, 00), Clock::new(15, 15));
-        let break2 = Range::new(Clock::new(17, 15), Clock::new(17, 30));
-        let break3 = Range::new(Clock::new(19, 30), Clock::new(19, 45));
-
-        match self {
-            MemberKind::FullTime => vec![break0, break1, break2],
-            MemberKind::Associate => vec![break0],
-            MemberKind::PartTimeA => vec![break0],
-            MemberKind::PartTimeB => vec![break0],
-            MemberKind::PartTimeC => vec![break0],
-            MemberKind::PartTimeD => vec![break0, break1, break2],
-            _ => vec![],
-        }
-    }
-
-    pub fn print_force_breaks(&self) -> String {
-        // 休憩15:00[有り],休憩15:00[無し],休憩17:00[有り],休憩17:00[無し]
-
-        match self {
-            MemberKind::FullTime => "1,,1,".to_string(),
-            MemberKind::Associate => ",1,,1".to_string(),
-            MemberKind::PartTimeA => ",1,,1".to_string(),
-            MemberKind::PartTimeB => ",1,,1".to_string(),
-            MemberKind::PartTimeC => ",1,,1".to_string(),
-            MemberKind::PartTimeD => "1,,1,".to_string(),
-            _ => ",,,".to_string(),
-        }
+    pub fn new<T: Into<String>>(key: T) -> Self {
+        Self(key.into())
     }
 }
 
-impl FromStr for MemberKind {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let member_type = match s {
-            "LUC社員" => MemberKind::FullTime,
-            "LUC準社員" => MemberKind::Associate,
-            "役員" => MemberKind::FullTime,
-            "A" => MemberKind::PartTimeA,
-            "B" => MemberKind::PartTimeB,
-            "C" => MemberKind::PartTimeC,
-            "D" => MemberKind::PartTimeD,
-            _ => MemberKind::Unknown,
-        };
-        Ok(member_type)
+impl Display for MemberKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -72,33 +31,134 @@ pub struct Member {
     pub name: String,
     pub member_type: MemberKind,
     pub from: String,
+    pub schedule: Schedule,
 }
 
 impl Member {
-    pub fn new<T: Into<String>>(id: u16, name: T, member_type: MemberKind, from: T) -> Self {
+    pub fn new<T: Into<String>>(
+        id: u16,
+        name: T,
+        member_type: MemberKind,
+        from: T,
+        schedule: Schedule,
+    ) -> Self {
         Self {
             id,
             name: name.into(),
             member_type,
             from: from.into(),
+            schedule,
         }
     }
 
-    pub fn from_strs(id: &str, name: &str, member_type: &str, from: &str) -> anyhow::Result<Self> {
-        Ok(Self::new(
-            id.parse()?,
-            name,
-            member_type.parse::<MemberKind>()?,
-            from,
-        ))
+    pub fn from_strs(
+        rules: &Rules,
+        id: &str,
+        name: &str,
+        member_type: &str,
+        from: &str,
+    ) -> anyhow::Result<Self> {
+        let id = id
+            .parse()
+            .with_context(|| format!("Invalid member id: {}", id))?;
+        let member_type = rules.kind_of(member_type)?;
+        let schedule = rules.schedule(&member_type)?.clone();
+        Ok(Self::new(id, name, member_type, from, schedule))
+    }
+
+    // 名簿の個人別の列で、区分の規定出勤時刻・規定退勤時刻・強制休憩・所定労働時間を上書きする。空欄は区分のまま
+    pub fn override_schedule(
+        &mut self,
+        start_at: &str,
+        end_at: &str,
+        force_breaks: &str,
+        nominal_work_time: &str,
+    ) -> anyhow::Result<()> {
+        let start_at: Cell<Clock> = Cell::from_field(start_at).context("規定出勤時刻")?;
+        // 翌日の規定退勤時刻は「翌1:00」か「25:00」と書く
+        let end_at = match end_at.trim() {
+            "" => Cell::NoData,
+            e => Cell::new(Clock::parse_extended(e).context("規定退勤時刻")?),
+        };
+        let nominal_work_time: Cell<Time> =
+            Cell::from_field(nominal_work_time).context("所定労働時間")?;
+
+        // 規定出勤時刻か所定労働時間だけを変えた場合、規定退勤時刻はそこから求める
+        if start_at.peek().is_ok() || nominal_work_time.peek().is_ok() {
+            self.schedule.explicit_end_at = None;
+        }
+        if let Ok(s) = start_at.peek() {
+            self.schedule.start_at = s.clone();
+        }
+        if let Ok(e) = end_at.peek() {
+            self.schedule.explicit_end_at = Some(e.clone());
+        }
+        if let Ok(n) = nominal_work_time.peek() {
+            self.schedule.nominal_work_time = n.clone();
+        }
+        if let Some(e) = &self.schedule.explicit_end_at {
+            if !e.later_than(&self.schedule.start_at) {
+                return Err(anyhow!(
+                    "規定退勤時刻 {} must be after 規定出勤時刻 {}",
+                    e,
+                    self.schedule.start_at
+                ));
+            }
+        }
+        match force_breaks.trim() {
+            "" => {}
+            "なし" => self.schedule.force_breaks = vec![],
+            breaks => {
+                self.schedule.force_breaks = breaks
+                    .split([' ', '/', '・', '、'])
+                    .filter(|b| !b.is_empty())
+                    .map(|b| {
+                        b.parse::<Range>()
+                            .with_context(|| format!("強制休憩：{}", b))
+                    })
+                    .collect::<anyhow::Result<Vec<Range>>>()?
+            }
+        }
+        Ok(())
     }
 
     pub fn start_at(&self) -> Clock {
-        match self.member_type {
-            MemberKind::FullTime => Clock::new(8, 30),
-            MemberKind::Associate => Clock::new(8, 30),
-            _ => Clock::new(9, 0),
+        self.schedule.start_at.clone()
+    }
+
+    pub fn end_at(&self) -> Clock {
+        self.schedule.end_at()
+    }
+
+    pub fn force_breaks(&self) -> Vec<Range> {
+        self.schedule.force_breaks.clone()
+    }
+
+    pub fn force_break_flags(&self) -> Vec<String> {
+        // 休憩15:00[有り],休憩15:00[無し],休憩17:00[有り],休憩17:00[無し]
+
+        if self.schedule.force_breaks.is_empty() {
+            return vec!["".to_string(); 4];
         }
+
+        [15, 17]
+            .iter()
+            .flat_map(|hours| {
+                match self
+                    .schedule
+                    .force_breaks
+                    .iter()
+                    .any(|b| b.start.hours == *hours)
+                {
+                    true => vec!["1".to_string(), "".to_string()],
+                    false => vec!["".to_string(), "1".to_string()],
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_fields(&self) -> Vec<String> {
+        vec![self.id.to_string(), self.name.clone()]
     }
 }
 
@@ -108,26 +168,80 @@ impl Display for Member {
     }
 }
 
-pub fn collect_from_csv<R: BufRead>(reader: R) -> HashSet<Member> {
-    reader
-        .lines()
-        .filter_map(|line| {
-            line.ok().and_then(|l| {
-                let trimmed = l.replace("\"", "");
-                let columns: Vec<&str> = trimmed.split(",").collect();
-                if columns.len() < 3 {
-                    return None;
-                }
-                match Member::from_strs(
-                    columns[0],
-                    columns[1],
-                    columns[2],
-                    if columns.len() > 3 { columns[3] } else { "" },
-                ) {
-                    Ok(m) => Some(m),
-                    _ => None,
-                }
-            })
-        })
-        .collect()
+pub fn collect_from_csv<R: Read>(
+    reader: R,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> HashSet<Member> {
+    let mut roster = HashSet::new();
+    for (i, row) in read_rows(reader, FILE_NAME, diagnostics).iter().enumerate() {
+        // 1行目は社員番号が数字でなければ見出しとみなす
+        if row.is_blank() || (i == 0 && row.get(0).trim().parse::<u16>().is_err()) {
+            continue;
+        }
+        let result = Member::from_strs(rules, row.get(0), row.get(1), row.get(2), row.get(3))
+            .and_then(|mut m| {
+                m.override_schedule(row.get(4), row.get(5), row.get(6), row.get(7))?;
+                Ok(m)
+            });
+        match result {
+            Ok(m) => {
+                roster.insert(m);
+            }
+            Err(e) => diagnostics.reject(FILE_NAME, row, &e),
+        }
+    }
+    roster
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, Range, Time};
+    use crate::diagnostics::Diagnostics;
+    use crate::member::collect_from_csv;
+    use crate::rules::Rules;
+    use std::io::Cursor;
+
+    #[test]
+    fn override_schedule() {
+        let rules = Rules::default_rules().unwrap();
+        let mut diagnostics = Diagnostics::new();
+        let roster = collect_from_csv(
+            Cursor::new(
+                "社員番号,氏名,区分,派遣元,規定出勤時刻,規定退勤時刻,強制休憩,所定労働時間\n\
+                 1,山田太郎,A,,10:00,,なし,5:00\n\
+                 2,佐藤花子,A,,,16:00,10:30-10:40 15:00-15:15,\n\
+                 3,鈴木一郎,A,,\n\
+                 4,高橋次郎,A,,10時,,,\n\
+                 5,田中三郎,LUC社員,,13:00,,,\n\
+                 6,伊藤四郎,A,,21:00,24:30,,\n\
+                 7,渡辺五郎,A,,21:00,1:00,,\n",
+            ),
+            &rules,
+            &mut diagnostics,
+        );
+        assert_eq!(roster.len(), 5);
+        assert_eq!(diagnostics.rejected_count(), 2);
+
+        let member = |id: u16| roster.iter().find(|m| m.id == id).unwrap();
+        assert_eq!(member(1).start_at(), Clock::new(10, 0));
+        assert_eq!(member(1).end_at(), Clock::new(15, 50));
+        assert!(member(1).force_breaks().is_empty());
+        assert_eq!(member(1).schedule.nominal_work_time, Time::new(5, 0));
+
+        assert_eq!(member(2).start_at(), Clock::new(9, 0));
+        assert_eq!(member(2).end_at(), Clock::new(16, 0));
+        assert_eq!(
+            member(2).force_breaks()[1],
+            Range::new(Clock::new(15, 0), Clock::new(15, 15))
+        );
+
+        assert_eq!(member(3).end_at(), Clock::new(18, 0));
+
+        // 規定出勤時刻だけを変えると、勤務中の昼休憩・強制休憩から規定退勤時刻を求め直す
+        assert_eq!(member(5).start_at(), Clock::new(13, 0));
+        assert_eq!(member(5).end_at(), Clock::new(21, 30));
+
+        assert_eq!(member(6).end_at(), Clock::new(0, 30).next_day());
+    }
 }
@@ -0,0 +1,266 @@
+use crate::cell::Cell;
+use crate::clock::Time;
+use crate::member::Member;
+use crate::record::{self, DayCounts, Record};
+use crate::rules::Rules;
+use crate::total::Total;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Match,
+    Differs,
+    MissingInTotals,
+    MissingInRecords,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Status::Match => "",
+            Status::Differs => "差あり",
+            Status::MissingInTotals => "PCA給与Xに無い",
+            Status::MissingInRecords => "出勤簿に無い",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// 社員ごとの、PCA給与Xの値と出勤簿から補正した値の照合
+#[derive(Debug)]
+pub struct Reconciliation {
+    pub member: Member,
+    pub work_time: Cell<Time>,
+    pub rounded_work_time: Cell<Time>,
+    // 日数はPCA給与Xに半日（0.5）などの小数があるので小数で持つ
+    pub work_days: Cell<f32>,
+    pub counted_work_days: Cell<f32>,
+    pub holiday_work_days: Cell<f32>,
+    pub counted_holiday_work_days: Cell<f32>,
+    pub absence_days: Cell<f32>,
+    pub counted_absence_days: Cell<f32>,
+    pub over_work_time: Cell<Time>,
+    pub rounded_over_work_time: Cell<Time>,
+    pub status: Status,
+}
+
+impl Reconciliation {
+    pub fn diff_work_time(&self) -> anyhow::Result<Time> {
+        diff_time(&self.work_time, &self.rounded_work_time)
+    }
+
+    pub fn diff_work_days(&self) -> anyhow::Result<f32> {
+        diff_days(&self.work_days, &self.counted_work_days)
+    }
+
+    pub fn diff_holiday_work_days(&self) -> anyhow::Result<f32> {
+        diff_days(&self.holiday_work_days, &self.counted_holiday_work_days)
+    }
+
+    pub fn diff_absence_days(&self) -> anyhow::Result<f32> {
+        diff_days(&self.absence_days, &self.counted_absence_days)
+    }
+
+    pub fn diff_over_work_time(&self) -> anyhow::Result<Time> {
+        diff_time(&self.over_work_time, &self.rounded_over_work_time)
+    }
+
+    fn differs(&self, rules: &Rules) -> bool {
+        let limit = rules.reconcile_work_time.as_minutes();
+        let times = [self.diff_work_time(), self.diff_over_work_time()];
+        let days = [
+            self.diff_work_days(),
+            self.diff_holiday_work_days(),
+            self.diff_absence_days(),
+        ];
+        times
+            .iter()
+            .any(|d| d.as_ref().is_ok_and(|d| d.as_minutes() >= limit))
+            || days.iter().any(|d| {
+                d.as_ref()
+                    .is_ok_and(|d| d.abs() >= rules.reconcile_work_days as f32)
+            })
+    }
+
+    pub fn export_to_csv(&self) -> Vec<String> {
+        let mut buf = self.member.to_fields();
+        let or_empty = |d: anyhow::Result<Time>| d.map_or("".to_string(), |d| d.to_string());
+        let days_or_empty = |d: anyhow::Result<f32>| d.map_or("".to_string(), |d| d.to_string());
+        buf.append(&mut vec![
+            self.work_time.to_string(),
+            self.rounded_work_time.to_string(),
+            or_empty(self.diff_work_time()),
+            self.work_days.to_string(),
+            self.counted_work_days.to_string(),
+            days_or_empty(self.diff_work_days()),
+            self.holiday_work_days.to_string(),
+            self.counted_holiday_work_days.to_string(),
+            days_or_empty(self.diff_holiday_work_days()),
+            self.absence_days.to_string(),
+            self.counted_absence_days.to_string(),
+            days_or_empty(self.diff_absence_days()),
+            self.over_work_time.to_string(),
+            self.rounded_over_work_time.to_string(),
+            or_empty(self.diff_over_work_time()),
+            self.status.to_string(),
+        ]);
+        buf
+    }
+}
+
+fn diff_time(original: &Cell<Time>, corrected: &Cell<Time>) -> anyhow::Result<Time> {
+    Ok(corrected
+        .peek()?
+        .clone()
+        .sub_allow_negative(original.peek()?))
+}
+
+// 小数の誤差が表示に出ないよう、差は0.01日単位に丸める
+fn diff_days(original: &Cell<f32>, counted: &Cell<f32>) -> anyhow::Result<f32> {
+    Ok(((counted.peek()? - original.peek()?) * 100.).round() / 100.)
+}
+
+// totals は集計済みのもの。PCA給与Xの順に並べ、出勤簿にしかいない社員を社員番号順に続ける
+pub fn reconcile(totals: &[Total], records: &[Record], rules: &Rules) -> Vec<Reconciliation> {
+    let mut result = vec![];
+    for t in totals.iter() {
+        let member = match t.member.peek() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        let the_records: Vec<&Record> = records.iter().filter(|r| r.member == t.member).collect();
+        let counted = |f: fn(&DayCounts) -> u8| match t.counted_days.peek() {
+            Ok(c) => Cell::new(f(c) as f32),
+            Err(_) => Cell::NoData,
+        };
+        let mut r = Reconciliation {
+            member: member.clone(),
+            work_time: t.total_work_time.clone(),
+            rounded_work_time: t.rounded_work_time.clone(),
+            work_days: t.work_days.clone(),
+            counted_work_days: counted(|c| c.work_days),
+            holiday_work_days: t.original_days("休日出勤日数"),
+            counted_holiday_work_days: counted(|c| c.holiday_work_days),
+            absence_days: t.original_days("事故欠勤日数"),
+            counted_absence_days: counted(|c| c.absence_days),
+            over_work_time: Cell::new(t.original_over_work_time()),
+            rounded_over_work_time: Cell::new(t.corrected_over_work_time()),
+            status: Status::Match,
+        };
+        r.status = match (the_records.is_empty(), r.differs(rules)) {
+            (true, _) => Status::MissingInRecords,
+            (false, true) => Status::Differs,
+            (false, false) => Status::Match,
+        };
+        result.push(r);
+    }
+
+    let mut missing: Vec<&Member> = vec![];
+    for r in records.iter() {
+        if let Ok(m) = r.member.peek() {
+            let in_totals = totals.iter().any(|t| t.member.peek().is_ok_and(|n| n == m));
+            if !in_totals && !missing.contains(&m) {
+                missing.push(m);
+            }
+        }
+    }
+    missing.sort_by_key(|m| m.id);
+    for m in missing {
+        let the_records: Vec<&Record> = records
+            .iter()
+            .filter(|r| r.member.peek().is_ok_and(|n| n == m))
+            .collect();
+        let rounded_work_time = the_records.iter().fold(Time::new(0, 0), |sum, r| {
+            sum.merge(&r.rounded_work_time().unwrap_or(Time::new(0, 0)))
+        });
+        let counted = record::count_days(&the_records, rules);
+        result.push(Reconciliation {
+            member: m.clone(),
+            work_time: Cell::NoData,
+            rounded_work_time: Cell::new(rounded_work_time),
+            work_days: Cell::NoData,
+            counted_work_days: Cell::new(counted.work_days as f32),
+            holiday_work_days: Cell::NoData,
+            counted_holiday_work_days: Cell::new(counted.holiday_work_days as f32),
+            absence_days: Cell::NoData,
+            counted_absence_days: Cell::new(counted.absence_days as f32),
+            over_work_time: Cell::NoData,
+            rounded_over_work_time: Cell::NoData,
+            status: Status::MissingInTotals,
+        });
+    }
+    result
+}
+
+pub fn get_csv_headings() -> &'static str {
+    "社員コード,氏名,出勤時間,補正出勤時間,出勤時間の差,出勤日数,出勤簿の出勤日数,出勤日数の差,休日出勤日数,出勤簿の休日出勤日数,休日出勤日数の差,事故欠勤日数,出勤簿の欠勤日数,事故欠勤日数の差,残業時間,補正残業時間,残業時間の差,判定"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use crate::reconcile::{reconcile, Status};
+    use crate::rules::Rules;
+    use crate::Time;
+
+    #[test]
+    fn reconciliation() {
+        let rules = Rules::default_rules().unwrap();
+        let converted = fixtures::convert(
+            &rules,
+            "1,山田太郎,LUC社員,\n2,佐藤花子,A,\n3,鈴木一郎,A,\n",
+            "",
+            "2026/10,1,山田太郎,10/1,木,8:20,19:40,1:30,9:50,,1\n\
+             2026/10,3,鈴木一郎,10/1,木,9:00,17:10,0:10,7:00,,1\n",
+            &(fixtures::totals_headings() + "1,20,160:00,1,9:50\n2,20,160:00,2,16:00\n"),
+        );
+
+        let found = reconcile(&converted.totals, &converted.records, &rules);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].status, Status::Differs);
+        assert_eq!(
+            found[0].diff_work_time().unwrap(),
+            Time::new_as_negative(1, 20)
+        );
+        assert_eq!(found[0].diff_work_days().unwrap(), 0.);
+        assert_eq!(found[0].diff_over_work_time().unwrap(), Time::new(0, 30));
+        assert_eq!(found[1].status, Status::MissingInRecords);
+        assert_eq!(found[2].member.id, 3);
+        assert_eq!(found[2].status, Status::MissingInTotals);
+        assert_eq!(found[2].export_to_csv()[3], "07.00");
+
+        // 日数の小数も読み、差は日数で比べる
+        let converted = fixtures::convert(
+            &rules,
+            "1,山田太郎,LUC社員,\n",
+            "",
+            "2026/10,1,山田太郎,10/1,木,8:20,19:40,1:30,9:50,,1\n\
+             2026/10,1,山田太郎,10/2,金,8:20,12:00,0:00,3:40,,0.5\n",
+            &(fixtures::totals_headings() + "1,20.0,160:00,1.5,13:30\n"),
+        );
+        assert!(converted.diagnostics.entries.is_empty());
+        let found = reconcile(&converted.totals, &converted.records, &rules);
+        assert_eq!(found[0].diff_work_days().unwrap(), 0.5);
+        assert_eq!(converted.totals[0].export_to_csv()[2], "20");
+    }
+
+    #[test]
+    fn night_shift() {
+        let rules = Rules::default_rules().unwrap();
+        let converted = fixtures::convert(
+            &rules,
+            "1,佐藤花子,A,\n",
+            "",
+            "2026/10,1,佐藤花子,10/1,木,21:00,翌6:00,0:00,9:00,,1\n",
+            &(fixtures::totals_headings() + "1,20,160:00,1,9:00,0,0,0,0,0,0,1:00,0:00\n"),
+        );
+
+        let found = reconcile(&converted.totals, &converted.records, &rules);
+        assert_eq!(
+            found[0].rounded_over_work_time.peek().unwrap(),
+            &Time::new(1, 0)
+        );
+        assert_eq!(found[0].diff_over_work_time().unwrap(), Time::new(0, 0));
+        assert_eq!(found[0].status, Status::Match);
+    }
+}
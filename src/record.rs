@@ -1,8 +1,16 @@
+use crate::calendar::Calendar;
 use crate::cell::Cell;
 use crate::clock::{Clock, Date, DateKind, DayKind, Month, Range, Time};
+use crate::diagnostics::Diagnostics;
 use crate::member::Member;
+use crate::rules::Rules;
+use crate::table::{read_rows, Columns};
+use anyhow::Context;
+use chrono::{Datelike, Duration, NaiveDate};
 use std::collections::HashSet;
-use std::io::BufRead;
+use std::io::Read;
+
+pub const FILE_NAME: &str = "出勤簿.csv";
 
 #[derive(Debug)]
 pub struct Record {
@@ -12,16 +20,20 @@ pub struct Record {
     pub day: Cell<DayKind>,
     pub came_at: Cell<Clock>,
     pub left_at: Cell<Clock>,
+    // 「翌」や24時以降の表記が無いのに、出勤時刻より前なので翌日とみなした
+    pub implicit_next_day: bool,
     pub break_time: Cell<Time>,
     pub work_time: Cell<Time>,
     pub remarks: Cell<String>,
-    pub days: Cell<u8>,
+    // 半日出勤の0.5など小数もある
+    pub days: Cell<f32>,
 }
 
 impl Record {
+    #[allow(clippy::too_many_arguments)]
     pub fn from_strs(
         roster: &HashSet<Member>,
-        off_list: &[Date],
+        calendar: &Calendar,
         month: &str,
         member_id: &str,
         date: &str,
@@ -33,84 +45,250 @@ impl Record {
         remarks: &str,
         days: &str,
     ) -> anyhow::Result<Self> {
-        let member_id: u16 = member_id.parse()?;
+        let member_id: u16 = member_id
+            .parse()
+            .with_context(|| format!("Invalid member id: {}", member_id))?;
         let member = roster
             .iter()
             .find(|m| m.id == member_id)
-            .ok_or(anyhow!("No member has been found"))?
+            .ok_or(anyhow!("No member has been found: {}", member_id))?
             .to_owned();
+        let came_at: Cell<Clock> = Cell::from_field(came_at).context("出勤時刻")?;
+        let (left_at, implicit_next_day) = parse_left_at(left_at, &came_at).context("退勤時刻")?;
+
+        let month: Cell<Month> = Cell::from_field(month).context("年月")?;
+        let date = Cell::new(date.parse::<Date>().context("日付")?);
+        let date = match month.peek() {
+            Ok(m) => date.map(|d| d.resolve(m).annotate(calendar)),
+            Err(_) => date,
+        };
+        // 日付が分かれば曜日は日付から求める
+        let day = match date.peek() {
+            Ok(d) if d.calendar_date.is_some() => Cell::new(d.day_kind()),
+            _ => day.parse()?,
+        };
 
         Ok(Self {
-            month: month.parse()?,
+            month,
             member: Cell::new(member),
-            date: date
-                .parse::<Cell<Date>>()
-                .unwrap()
-                .map(|d| d.annotate(off_list)),
-            day: day.parse()?,
-            came_at: came_at.parse()?,
-            left_at: left_at.parse()?,
-            break_time: break_time.parse::<Cell<Time>>()?.or(Time::new(0, 0)),
-            work_time: work_time.parse()?,
+            date,
+            day,
+            came_at,
+            left_at,
+            implicit_next_day,
+            break_time: Cell::<Time>::from_field(break_time)
+                .context("休憩時間")?
+                .or(Time::new(0, 0)),
+            work_time: Cell::from_field(work_time).context("労働時間")?,
             remarks: remarks.parse()?,
-            days: days.parse()?,
+            days: Cell::from_field(days).context("出勤日数")?,
         })
     }
 
-    pub fn rounded_work_time(&self) -> anyhow::Result<Time> {
+    pub fn calendar_date(&self) -> anyhow::Result<NaiveDate> {
+        let date = self.date.peek()?;
+        date.calendar_date.ok_or(anyhow!("Invalid date: {}", date))
+    }
+
+    // 補正労働時間の計算。途中経過を Breakdown に残し、explain などでそのまま表示できるようにする
+    pub fn breakdown(&self) -> anyhow::Result<Breakdown> {
+        let member = self.member.peek()?;
         let came_at = self.came_at.peek()?.clone();
-        let mut start_at = self.member.peek()?.clone().start_at();
-        start_at = match came_at.later_than(&start_at) {
+        let scheduled_start_at = member.start_at();
+        let start_rounded = came_at.later_than(&scheduled_start_at);
+        let mut start_at = match start_rounded {
             true => came_at.round_up(),
-            false => start_at,
+            false => scheduled_start_at.clone(),
         };
-        let left_at = self.left_at.peek()?.clone().round_down();
-        let mut work_time = Time::new(0, 0);
+        let effective_start_at = start_at.clone();
+        let raw_left_at = self.left_at.peek()?.clone();
+        let left_at = raw_left_at.round_down();
+        let lunch = member.schedule.lunch.clone();
+        let mut lunches = vec![];
+        let mut ranges = vec![];
+        let mut finished = false;
 
-        let start_lunch_at = Clock::new(12, 10);
-        let mut work_time_am = match left_at.later_than(&start_lunch_at) {
-            true => start_lunch_at.diff(&start_at),
-            false => left_at.diff(&start_at),
-        };
+        for lunch in [lunch.clone(), lunch.next_day()].iter() {
+            if !start_at.later_than(&lunch.start) {
+                let end_am_at = match raw_left_at.or_later_than(&lunch.start) {
+                    true => lunch.start.clone(),
+                    false => left_at.clone(),
+                };
+                if end_am_at.later_than(&start_at) {
+                    ranges.push(Range::new(start_at.clone(), end_am_at));
+                }
+                if !raw_left_at.or_later_than(&lunch.start) {
+                    finished = true;
+                    break;
+                }
+                lunches.push((lunch.clone(), LunchStep::Taken));
+                start_at = lunch.end.clone();
+            } else if !start_at.later_than(&lunch.end) {
+                lunches.push((lunch.clone(), LunchStep::StartedDuring));
+                start_at = lunch.end.clone();
+            }
+        }
 
-        if left_at == Clock::new(12, 0) && self.left_at.peek()?.or_later_than(&start_lunch_at) {
-            work_time_am = start_lunch_at.diff(&start_at);
+        if !finished && left_at.later_than(&start_at) {
+            ranges.push(Range::new(start_at, left_at.clone()));
         }
 
-        if !start_at.later_than(&start_lunch_at) {
-            work_time = work_time.merge(&work_time_am);
+        // 勤務中に無い強制休憩は、入力の休憩時間から除く
+        let input_break_time = self.break_time.peek()?.clone();
+        let mut break_time = input_break_time.clone();
+        let range = Range::new(came_at.clone(), raw_left_at.clone());
+        let mut force_breaks = vec![];
+        for f in member.force_breaks().iter() {
+            let step = match range.includes(f) || range.includes(&f.next_day()) {
+                true => ForceBreakStep::Taken,
+                false => {
+                    // 休憩時間は0未満にならないので、実際に除けた分を残す
+                    let rest = break_time.clone().sub(&f.abs());
+                    let excluded = break_time.sub(&rest);
+                    break_time = rest;
+                    ForceBreakStep::Excluded(excluded)
+                }
+            };
+            force_breaks.push((f.clone(), step));
         }
 
-        let end_lunch_at = Clock::new(13, 0);
-        let start_at_pm = match start_at.later_than(&end_lunch_at) {
-            true => start_at,
-            false => end_lunch_at,
-        };
+        let worked = ranges
+            .iter()
+            .fold(Time::new(0, 0), |sum, r| sum.merge(&r.abs()));
+        let unrounded_work_time = worked.clone().sub(&break_time);
+        let rounded_work_time = unrounded_work_time.round_down();
 
-        let work_time_pm = match left_at.later_than(&start_at_pm) {
-            true => left_at.diff(&start_at_pm),
-            false => Time::new(0, 0),
-        };
-        work_time = work_time.merge(&work_time_pm);
+        Ok(Breakdown {
+            came_at,
+            scheduled_start_at,
+            start_rounded,
+            effective_start_at,
+            raw_left_at,
+            left_at,
+            lunches,
+            ranges,
+            worked,
+            input_break_time,
+            force_breaks,
+            break_time,
+            unrounded_work_time,
+            rounded_work_time,
+        })
+    }
 
-        work_time = work_time.sub(&self.break_time()?);
-        Ok(work_time.round_down())
+    pub fn work_ranges(&self) -> anyhow::Result<Vec<Range>> {
+        Ok(self.breakdown()?.ranges)
     }
 
-    pub fn over_work_time(&self) -> anyhow::Result<Time> {
-        let nominal = match self.date.clone().data()?.date_type {
-            DateKind::On => Ok(Time::new(8, 0)),
-            DateKind::Off => Ok(Time::new(0, 0)),
+    pub fn rounded_work_time(&self) -> anyhow::Result<Time> {
+        Ok(self.breakdown()?.rounded_work_time)
+    }
+
+    pub fn late_night_work_time(&self) -> anyhow::Result<Time> {
+        let ranges = self.work_ranges()?;
+        let breaks = self.force_breaks_taken()?;
+        let mut late_night = Time::new(0, 0);
+
+        for night in late_night_ranges().iter() {
+            let worked = ranges
+                .iter()
+                .fold(Time::new(0, 0), |sum, r| sum.merge(&r.overlap(night)));
+            let rested = breaks
+                .iter()
+                .fold(Time::new(0, 0), |sum, b| sum.merge(&b.overlap(night)));
+            late_night = late_night.merge(&worked.sub(&rested));
+        }
+
+        let rounded = self.rounded_work_time()?;
+        match late_night.as_minutes() > rounded.as_minutes() {
+            true => Ok(rounded),
+            false => Ok(late_night.round_down()),
+        }
+    }
+
+    // 法定外労働時間は勤務の終わりの時間帯とみなし、そのうち深夜にかかる分
+    pub fn late_night_over_work_time(&self, over: &Time) -> anyhow::Result<Time> {
+        let mut remaining = over.clone();
+        let mut late_night = Time::new(0, 0);
+
+        for r in self.work_ranges()?.iter().rev() {
+            let tail = r.tail(&remaining);
+            for night in late_night_ranges().iter() {
+                late_night = late_night.merge(&tail.overlap(night));
+            }
+            remaining = remaining.sub(&tail.abs());
+        }
+
+        let limit = self.late_night_work_time()?;
+        match late_night.as_minutes() > limit.as_minutes() {
+            true => Ok(limit),
+            false => Ok(late_night),
+        }
+    }
+
+    // 遅刻した時間。出勤日に、切り上げた出勤時刻が規定出勤時刻より後になった分
+    pub fn late_time(&self) -> anyhow::Result<Time> {
+        if !self.is_on_day()? {
+            return Ok(Time::new(0, 0));
+        }
+        let b = self.breakdown()?;
+        match b.start_rounded {
+            true => Ok(b.effective_start_at.diff(&b.scheduled_start_at)),
+            false => Ok(Time::new(0, 0)),
+        }
+    }
+
+    // 早退した時間。出勤日に、切り捨てた退勤時刻が規定退勤時刻より前になった分
+    pub fn early_leave_time(&self) -> anyhow::Result<Time> {
+        if !self.is_on_day()? {
+            return Ok(Time::new(0, 0));
+        }
+        let left_at = self.breakdown()?.left_at;
+        let end_at = self.member.peek()?.end_at();
+        match end_at.later_than(&left_at) {
+            true => Ok(end_at.diff(&left_at)),
+            false => Ok(Time::new(0, 0)),
+        }
+    }
+
+    fn is_on_day(&self) -> anyhow::Result<bool> {
+        match self.date.peek()?.date_type {
+            DateKind::On => Ok(true),
+            DateKind::CompanyOff | DateKind::StatutoryOff => Ok(false),
             DateKind::Unknown => Err(anyhow!("DateKind is not annotated")),
+        }
+    }
+
+    pub fn normal_work_time(&self) -> anyhow::Result<Time> {
+        Ok(self.rounded_work_time()?.sub(&self.late_night_work_time()?))
+    }
+
+    // 法定外労働時間。出勤日は法定労働時間（8時間）を超えた分、休日は全て
+    pub fn over_work_time(&self) -> anyhow::Result<Time> {
+        let limit = match self.is_on_day()? {
+            true => statutory_work_time(),
+            false => Time::new(0, 0),
         };
+        Ok(self.rounded_work_time()?.sub(&limit))
+    }
 
-        nominal.and_then(|n| Ok(self.rounded_work_time()?.sub(&n)))
+    // 法定内残業時間。出勤日に所定労働時間を超え、法定労働時間までの分
+    pub fn within_over_work_time(&self) -> anyhow::Result<Time> {
+        if !self.is_on_day()? {
+            return Ok(Time::new(0, 0));
+        }
+        let nominal = &self.member.peek()?.schedule.nominal_work_time;
+        let rounded = self.rounded_work_time()?;
+        let within = match rounded.as_minutes() > statutory_work_time().as_minutes() {
+            true => statutory_work_time(),
+            false => rounded,
+        };
+        Ok(within.sub(nominal))
     }
 
-    pub fn export_rounded_to_csv(&self) -> anyhow::Result<String> {
-        let mut buf: Vec<String> = vec![];
-        buf.push(self.month.to_string());
-        buf.push(self.member.to_string());
+    pub fn export_rounded_to_csv(&self) -> anyhow::Result<Vec<String>> {
+        let mut buf: Vec<String> = vec![self.month.to_string()];
+        buf.append(&mut self.member.peek()?.to_fields());
         buf.push(self.date.to_string());
         buf.push(self.date.peek()?.date_type.to_string());
         buf.push(self.day.to_string());
@@ -126,28 +304,42 @@ impl Record {
                 .to_string(),
         );
         buf.push(self.over_work_time().unwrap_or(Time::new(0, 0)).to_string());
+        buf.push(
+            self.within_over_work_time()
+                .unwrap_or(Time::new(0, 0))
+                .to_string(),
+        );
+        buf.push(
+            self.normal_work_time()
+                .unwrap_or(Time::new(0, 0))
+                .to_string(),
+        );
+        buf.push(
+            self.late_night_work_time()
+                .unwrap_or(Time::new(0, 0))
+                .to_string(),
+        );
         buf.push(self.remarks.to_string());
         buf.push(self.days.to_string());
-        Ok(buf.join(","))
+        Ok(buf)
     }
 
-    pub fn export_rounded_to_daily_csv(&self, is_start: bool) -> anyhow::Result<String> {
-        let mut buf: Vec<String> = vec![];
-        buf.push(if is_start {
+    pub fn export_rounded_to_daily_csv(&self, is_start: bool) -> anyhow::Result<Vec<String>> {
+        let mut buf: Vec<String> = vec![if is_start {
             "*".to_string()
         } else {
             "".to_string()
-        });
+        }];
         buf.push(self.date.to_string());
-        buf.push(self.member.to_string());
+        buf.append(&mut self.member.peek()?.to_fields());
         buf.push(self.member.peek()?.from.clone());
         buf.push("出勤".to_string());
-        buf.push(match self.came_at {
-            Cell::Data(_) => "1,".to_string(),
-            Cell::NoData => ",1".to_string(),
+        buf.append(&mut match self.came_at {
+            Cell::Data(_) => vec!["1".to_string(), "".to_string()],
+            Cell::NoData => vec!["".to_string(), "1".to_string()],
         });
         buf.push(self.member.peek()?.start_at().to_string());
-        buf.push(self.member.peek()?.member_type.print_force_breaks());
+        buf.append(&mut self.member.peek()?.force_break_flags());
         buf.push(self.left_at.to_string());
         // work_time or rounded_work_time
         /*
@@ -159,58 +351,833 @@ impl Record {
         */
         buf.push(self.work_time.to_string().replace(".", ":"));
         buf.push(self.remarks.to_string());
-        Ok(buf.join(","))
+        Ok(buf)
+    }
+
+    // 補正内訳.csvの1行。計算できない記録は増減の欄を空にする
+    pub fn export_audit_to_csv(&self) -> anyhow::Result<Vec<String>> {
+        let mut buf: Vec<String> = vec![self.month.to_string()];
+        buf.append(&mut self.member.peek()?.to_fields());
+        buf.push(self.date.to_string());
+        buf.push(self.came_at.to_string());
+        buf.push(self.left_at.to_string());
+        match self.breakdown() {
+            Ok(b) => {
+                buf.append(&mut b.audit_fields());
+                buf.push(self.work_time.to_string());
+                buf.push(match self.work_time.peek() {
+                    Ok(w) => b.rounded_work_time.sub_allow_negative(w).to_string(),
+                    Err(_) => "".to_string(),
+                });
+            }
+            Err(_) => {
+                buf.append(&mut vec!["".to_string(); 9]);
+                buf.push(self.work_time.to_string());
+                buf.push("".to_string());
+            }
+        }
+        Ok(buf)
+    }
+
+    // 打刻漏れやありえない時刻など、読み込めても確認が必要な点
+    pub fn problems(&self, rules: &Rules) -> Vec<String> {
+        let mut problems = vec![];
+        match (&self.came_at, &self.left_at) {
+            (Cell::Data(_), Cell::NoData) => problems.push("退勤時刻がありません".to_string()),
+            (Cell::NoData, Cell::Data(_)) => problems.push("出勤時刻がありません".to_string()),
+            (Cell::Data(came_at), Cell::Data(left_at)) => {
+                if came_at.is_next_day() {
+                    problems.push(format!("出勤時刻 {} が24時以降です", came_at));
+                } else if !left_at.later_than(came_at) {
+                    problems.push(format!(
+                        "退勤時刻 {} が出勤時刻 {} より後ではありません",
+                        left_at, came_at
+                    ));
+                } else if left_at.as_minutes() - came_at.as_minutes() >= 60 * 24 {
+                    problems.push("出勤から退勤までが24時間以上あります".to_string());
+                } else if self.implicit_next_day {
+                    problems.push(format!(
+                        "退勤時刻が出勤時刻 {} より前なので翌日（{}）とみなしました",
+                        came_at, left_at
+                    ));
+                } else if let Ok(b) = self.break_time.peek() {
+                    if b.as_minutes() >= left_at.as_minutes() - came_at.as_minutes() {
+                        problems.push(format!(
+                            "休憩時間 {} が出勤から退勤までの時間以上あります",
+                            b
+                        ));
+                    }
+                }
+            }
+            (Cell::NoData, Cell::NoData) => {}
+        }
+
+        if let (Ok(month), Ok(date)) = (self.month.peek(), self.date.peek()) {
+            let period = month.period(rules.closing_day);
+            match (date.calendar_date, period) {
+                (Some(d), Ok((start, end))) if d < start || end < d => problems.push(format!(
+                    "{}は{}の締め期間（{}〜{}）の日付ではありません",
+                    date,
+                    month,
+                    start.format("%Y/%m/%d"),
+                    end.format("%Y/%m/%d")
+                )),
+                (Some(_), _) => {}
+                (None, _) => problems.push(format!("{}は存在しない日付です", date)),
+            }
+        }
+
+        if let Ok(date) = self.date.peek() {
+            let off = matches!(
+                date.date_type,
+                DateKind::CompanyOff | DateKind::StatutoryOff
+            );
+            if off && self.came_at.peek().is_ok() && self.remarks.to_string().trim().is_empty() {
+                problems.push(format!(
+                    "{}（{}）の出勤に備考がありません",
+                    date, date.date_type
+                ));
+            }
+        }
+
+        problems
     }
 
     pub fn break_time(&self) -> anyhow::Result<Time> {
-        let mut result = self.break_time.peek()?.clone();
-        let forces = self.member.peek()?.member_type.force_breaks();
+        Ok(self.breakdown()?.break_time)
+    }
+
+    fn force_breaks_taken(&self) -> anyhow::Result<Vec<Range>> {
         let range = Range::new(self.came_at.peek()?.clone(), self.left_at.peek()?.clone());
-        for f in forces.iter() {
-            if !range.includes(f) {
-                result = result.sub(&f.abs());
+        Ok(self
+            .member
+            .peek()?
+            .force_breaks()
+            .iter()
+            .flat_map(|f| vec![f.clone(), f.next_day()])
+            .filter(|f| range.includes(f))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LunchStep {
+    // 昼休憩をはさんで勤務した
+    Taken,
+    // 昼休憩中に勤務を始めた
+    StartedDuring,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForceBreakStep {
+    // 勤務中に取ったので、休憩時間に含める
+    Taken,
+    // 勤務中に無いので、休憩時間から除いた（除いた時間）
+    Excluded(Time),
+}
+
+// 補正労働時間の計算の途中経過
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    pub came_at: Clock,
+    pub scheduled_start_at: Clock,
+    // 規定出勤時刻より遅く出勤したので、出勤時刻を切り上げた
+    pub start_rounded: bool,
+    pub effective_start_at: Clock,
+    pub raw_left_at: Clock,
+    pub left_at: Clock,
+    pub lunches: Vec<(Range, LunchStep)>,
+    pub ranges: Vec<Range>,
+    pub worked: Time,
+    pub input_break_time: Time,
+    pub force_breaks: Vec<(Range, ForceBreakStep)>,
+    pub break_time: Time,
+    pub unrounded_work_time: Time,
+    pub rounded_work_time: Time,
+}
+
+impl Breakdown {
+    // explain で表示する、人が読むための計算の流れ
+    pub fn trace(&self) -> Vec<String> {
+        let mut lines = vec![];
+        lines.push(match self.start_rounded {
+            true => format!(
+                "開始 {}：出勤 {} が規定出勤時刻 {} より遅いので15分単位で切り上げ",
+                self.effective_start_at, self.came_at, self.scheduled_start_at
+            ),
+            false => format!(
+                "開始 {}：出勤 {} が規定出勤時刻 {} 以前なので規定出勤時刻から",
+                self.effective_start_at, self.came_at, self.scheduled_start_at
+            ),
+        });
+        lines.push(format!(
+            "終了 {}：退勤 {} を15分単位で切り捨て",
+            self.left_at, self.raw_left_at
+        ));
+        for (lunch, step) in self.lunches.iter() {
+            lines.push(match step {
+                LunchStep::Taken => format!("昼休憩 {}：勤務から除く", lunch),
+                LunchStep::StartedDuring => {
+                    format!("昼休憩 {}：昼休憩中に始めたので {} から", lunch, lunch.end)
+                }
+            });
+        }
+        for r in self.ranges.iter() {
+            lines.push(format!("勤務 {}：{}", r, r.abs()));
+        }
+        if self.ranges.is_empty() {
+            lines.push("勤務 なし".to_string());
+        }
+        lines.push(format!("勤務の合計 {}", self.worked));
+        lines.push(format!("入力の休憩時間 {}", self.input_break_time));
+        for (f, step) in self.force_breaks.iter() {
+            lines.push(match step {
+                ForceBreakStep::Taken => {
+                    format!("強制休憩 {}：勤務中なので休憩時間に含める", f)
+                }
+                ForceBreakStep::Excluded(t) => {
+                    format!("強制休憩 {}：勤務中に無いので休憩時間から {} を除く", f, t)
+                }
+            });
+        }
+        lines.push(format!("差し引く休憩時間 {}", self.break_time));
+        lines.push(format!(
+            "{} - {} = {} を15分単位で切り捨てて {}",
+            self.worked, self.break_time, self.unrounded_work_time, self.rounded_work_time
+        ));
+        lines
+    }
+
+    // 出勤から退勤までの時間と、補正労働時間になるまでの増減（減った分は負）
+    // 出勤から退勤まで＋各増減＝補正労働時間
+    pub fn audit_fields(&self) -> Vec<String> {
+        let minutes = |c: &Clock| c.as_minutes() as i32;
+        let span = minutes(&self.raw_left_at) - minutes(&self.came_at);
+        let start = minutes(&self.came_at) - minutes(&self.effective_start_at);
+        let end = minutes(&self.left_at) - minutes(&self.raw_left_at);
+        let lunch = self.worked.as_minutes() as i32
+            - (minutes(&self.left_at) - minutes(&self.effective_start_at));
+        let input_break = -(self.input_break_time.as_minutes() as i32);
+        let force_breaks =
+            self.input_break_time.as_minutes() as i32 - self.break_time.as_minutes() as i32;
+        let rounding = self.rounded_work_time.as_minutes() as i32
+            - (self.worked.as_minutes() as i32 - self.break_time.as_minutes() as i32);
+        // 強制休憩ごとの戻し。合計が強制休憩の戻しになる
+        let details: Vec<String> = self
+            .force_breaks
+            .iter()
+            .map(|(f, step)| match step {
+                ForceBreakStep::Taken => format!("{}：含める 00.00", f),
+                ForceBreakStep::Excluded(t) => format!("{}：除く {}", f, t),
+            })
+            .collect();
+
+        let time = |m: i32| Time::from_signed_minutes(m).to_string();
+        vec![
+            time(span),
+            time(start),
+            time(end),
+            time(lunch),
+            time(input_break),
+            time(force_breaks),
+            details.join("、"),
+            time(rounding),
+            self.rounded_work_time.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DayCounts {
+    pub work_days: u8,
+    pub holiday_work_days: u8,
+    pub absence_days: u8,
+}
+
+// 出勤日に出勤した日数、休日に出勤した日数、出勤日に打刻の無い日数。同じ日付は1日と数える
+// 備考が有休などの日は打刻が無くても欠勤としない
+pub fn count_days(records: &[&Record], rules: &Rules) -> DayCounts {
+    let mut counts = DayCounts::default();
+    let mut seen: Vec<String> = vec![];
+    for r in records.iter() {
+        let date = match r.date.peek() {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+        if seen.contains(&date.to_string()) {
+            continue;
+        }
+        seen.push(date.to_string());
+
+        let worked = r.came_at.peek().is_ok() || r.left_at.peek().is_ok();
+        let remarks = r.remarks.to_string();
+        let leave = rules
+            .leave_remarks
+            .iter()
+            .any(|l| !l.is_empty() && remarks.contains(l.as_str()));
+        match (&date.date_type, worked) {
+            (DateKind::On, true) => counts.work_days += 1,
+            (DateKind::On, false) if !leave => counts.absence_days += 1,
+            (DateKind::CompanyOff | DateKind::StatutoryOff, true) => counts.holiday_work_days += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
+// 給与を確定する前に確認が必要な記録の添字と理由。同じ社員の同じ日の記録は2件目以降を挙げる
+pub fn review(records: &[Record], rules: &Rules) -> Vec<(usize, String)> {
+    let mut found = vec![];
+    let mut seen: Vec<(u16, NaiveDate)> = vec![];
+    for (i, r) in records.iter().enumerate() {
+        for reason in r.problems(rules) {
+            found.push((i, reason));
+        }
+        if let (Ok(member), Ok(date)) = (r.member.peek(), r.calendar_date()) {
+            if seen.contains(&(member.id, date)) {
+                found.push((i, format!("{}の記録が重複しています", r.date)));
+            } else {
+                seen.push((member.id, date));
             }
         }
+    }
+    found
+}
+
+// 週の法定労働時間を超えた分を、日ごとの法定外労働時間と重ならないように各記録へ割り当てる
+pub fn weekly_over_work_times(records: &[&Record], rules: &Rules) -> Vec<Time> {
+    let mut result = vec![Time::new(0, 0); records.len()];
+    let mut dated: Vec<(usize, NaiveDate)> = records
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| r.calendar_date().ok().map(|d| (i, d)))
+        .collect();
+    dated.sort_by_key(|(_, d)| *d);
+
+    let week_of = |d: &NaiveDate| {
+        let offset =
+            (d.weekday().num_days_from_sunday() + 7 - rules.week_start.num_days_from_sunday()) % 7;
+        *d - Duration::days(offset as i64)
+    };
+
+    let mut week: Option<NaiveDate> = None;
+    let mut within = Time::new(0, 0);
+    for (i, d) in dated.iter() {
+        if week != Some(week_of(d)) {
+            week = Some(week_of(d));
+            within = Time::new(0, 0);
+        }
 
-        Ok(result)
+        let r = records[*i];
+        let daily = r
+            .rounded_work_time()
+            .unwrap_or(Time::new(0, 0))
+            .sub(&r.over_work_time().unwrap_or(Time::new(0, 0)));
+        let before = within.clone().sub(&rules.weekly_work_time);
+        within = within.merge(&daily);
+        result[*i] = within.clone().sub(&rules.weekly_work_time).sub(&before);
     }
+
+    result
+}
+
+// 退勤時刻が翌日の場合は24時以降の時刻として持つ
+// 「25:30」「翌1:30」のほか、出勤時刻より前の時刻も翌日とみなし、その場合は true を返す
+fn parse_left_at(s: &str, came_at: &Cell<Clock>) -> anyhow::Result<(Cell<Clock>, bool)> {
+    if s.trim().is_empty() {
+        return Ok((Cell::NoData, false));
+    }
+    let left_at = Clock::parse_extended(s).map_err(|_| anyhow!("Invalid value: {}", s))?;
+    let implicit = match came_at.peek() {
+        Ok(came_at) => !left_at.is_next_day() && came_at.later_than(&left_at),
+        Err(_) => false,
+    };
+    match implicit {
+        true => Ok((Cell::new(left_at.next_day()), true)),
+        false => Ok((Cell::new(left_at), false)),
+    }
+}
+
+// 1日の法定労働時間
+fn statutory_work_time() -> Time {
+    Time::new(8, 0)
 }
 
-pub fn collect_from_csv<R: BufRead>(
+// 深夜（22:00〜翌5:00）
+fn late_night_ranges() -> Vec<Range> {
+    vec![
+        Range::new(Clock::new(0, 0), Clock::new(5, 0)),
+        Range::new(Clock::new(22, 0), Clock::new(5, 0).next_day()),
+    ]
+}
+
+pub fn collect_from_csv<R: Read>(
     reader: R,
     roster: &HashSet<Member>,
-    off_list: &[Date],
-) -> Vec<Record> {
-    reader
-        .lines()
-        .flat_map(|line| {
-            line.ok().and_then(|l| {
-                let trimmed = l.replace("\"", "");
-                let mut columns = trimmed.split(",");
-                Record::from_strs(
-                    roster,
-                    off_list,
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.nth(1).unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                )
-                .ok()
-            })
-        })
-        .collect()
+    calendar: &Calendar,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Vec<Record>> {
+    let rows = read_rows(reader, FILE_NAME, diagnostics);
+    let headings = rows
+        .first()
+        .ok_or(anyhow!("{} has no heading row", FILE_NAME))?;
+    let columns = Columns::locate(
+        headings,
+        &[
+            "年月",
+            "社員番号",
+            "日付",
+            "出勤時刻",
+            "退勤時刻",
+            "休憩時間",
+            "労働時間",
+            "出勤日数",
+        ],
+        &["曜日", "備考"],
+        rules,
+        FILE_NAME,
+    )?;
+
+    let mut records = vec![];
+    let mut record_rows = vec![];
+    for row in rows.iter().skip(1).filter(|r| !r.is_blank()) {
+        let result = Record::from_strs(
+            roster,
+            calendar,
+            columns.get(row, "年月"),
+            columns.get(row, "社員番号"),
+            columns.get(row, "日付"),
+            columns.get(row, "曜日"),
+            columns.get(row, "出勤時刻"),
+            columns.get(row, "退勤時刻"),
+            columns.get(row, "休憩時間"),
+            columns.get(row, "労働時間"),
+            columns.get(row, "備考"),
+            columns.get(row, "出勤日数"),
+        );
+        match result {
+            Ok(record) => {
+                if let Some(reason) = day_mismatch(&record, columns.get(row, "曜日")) {
+                    diagnostics.warn(FILE_NAME, row, reason);
+                }
+                records.push(record);
+                record_rows.push(row);
+            }
+            Err(e) => diagnostics.reject(FILE_NAME, row, &e),
+        }
+    }
+
+    for (i, reason) in review(&records, rules) {
+        diagnostics.review(FILE_NAME, record_rows[i], reason);
+    }
+    Ok(records)
+}
+
+// 曜日欄が日付から求めた曜日と食い違っていれば理由を返す（集計には日付から求めた曜日を使う）
+fn day_mismatch(record: &Record, day: &str) -> Option<String> {
+    let input = day.trim().parse::<DayKind>().unwrap_or(DayKind::Unknown);
+    let date = record.date.peek().ok()?;
+    let derived = record.day.peek().ok()?;
+    match input != DayKind::Unknown && derived != &DayKind::Unknown && &input != derived {
+        true => Some(format!(
+            "{}は{}曜日ですが、曜日欄は{}になっています",
+            date, derived, input
+        )),
+        false => None,
+    }
 }
 
 pub fn get_csv_headings() -> &'static str {
-    "年月,社員番号,氏名,日付,日付区分,曜日,規定出勤時刻,出勤時刻,退勤時刻,休憩時間,労働時間,労働時間（HH:mm）,補正労働時間,法定外労働時間,備考,出勤日数"
+    "年月,社員番号,氏名,日付,日付区分,曜日,規定出勤時刻,出勤時刻,退勤時刻,休憩時間,労働時間,労働時間（HH:mm）,補正労働時間,法定外労働時間,法定内残業時間,補正通常労働時間,補正深夜労働時間,備考,出勤日数"
+}
+
+pub fn get_audit_csv_headings() -> &'static str {
+    "年月,社員番号,氏名,日付,出勤時刻,退勤時刻,出勤から退勤まで,開始の補正,終了の補正,昼休憩,休憩時間,強制休憩の戻し,強制休憩の内訳,切り捨て,補正労働時間,労働時間,補正との差"
 }
 
 pub fn get_daily_csv_headings() -> &'static str {
     "レコードの開始行,生産日,管理番号,作業者,派遣元,出勤,出勤[出勤],出勤[欠勤],開始_time1,休憩15:00[有り],休憩15:00[無し],休憩17:00[有り],休憩17:00[無し],退勤,勤務時間,備考"
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::{self, Calendar};
+    use crate::clock::{Clock, DayKind, Range, Time};
+    use crate::diagnostics::{Diagnostics, Severity};
+    use crate::fixtures;
+    use crate::member::Member;
+    use crate::record::{
+        collect_from_csv, weekly_over_work_times, ForceBreakStep, LunchStep, Record,
+    };
+    use crate::rules::Rules;
+    use chrono::NaiveDate;
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    // 社員番号1の社員だけの名簿と、休日の無いカレンダー
+    fn setup_with(rules: Rules, kind: &str) -> (Rules, HashSet<Member>, Calendar) {
+        let mut roster = HashSet::new();
+        roster.insert(Member::from_strs(&rules, "1", "山田太郎", kind, "").unwrap());
+        (rules, roster, Calendar::new(vec![], false, vec![]))
+    }
+
+    fn setup(kind: &str) -> (Rules, HashSet<Member>, Calendar) {
+        setup_with(Rules::default_rules().unwrap(), kind)
+    }
+
+    fn record(kind: &str, came_at: &str, left_at: &str, break_time: &str) -> Record {
+        let (_, roster, calendar) = setup(kind);
+
+        Record::from_strs(
+            &roster, &calendar, "2026/10", "1", "10/01", "木", came_at, left_at, break_time, "",
+            "", "1",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn invalid_fields() {
+        let (_, roster, calendar) = setup("A");
+        let from_strs = |work_time: &str, days: &str| {
+            Record::from_strs(
+                &roster, &calendar, "2026/10", "1", "10/01", "木", "9:00", "17:00", "", work_time,
+                "", days,
+            )
+        };
+
+        assert!(from_strs("", "").is_ok());
+        // 半日出勤の日数は小数のまま出力する
+        assert_eq!(from_strs("4:00", "0.5").unwrap().days.to_string(), "0.5");
+        let e = from_strs("7時間", "1").unwrap_err();
+        assert!(format!("{:#}", e).contains("労働時間"));
+        let e = from_strs("7:00", "一").unwrap_err();
+        assert!(format!("{:#}", e).contains("出勤日数"));
+    }
+
+    #[test]
+    fn rounded_work_time() {
+        let r = record("LUC社員", "8:20", "19:40", "1:30");
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(8, 30));
+        assert_eq!(r.over_work_time().unwrap(), Time::new(0, 30));
+
+        let r = record("A", "8:55", "12:05", "0:10");
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(2, 45));
+
+        let r = record("A", "9:00", "12:12", "0:10");
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(3, 0));
+    }
+
+    #[test]
+    fn breakdown() {
+        let r = record("A", "9:05", "15:10", "0:10");
+        let b = r.breakdown().unwrap();
+        assert!(b.start_rounded);
+        assert_eq!(b.effective_start_at, Clock::new(9, 15));
+        assert_eq!(b.left_at, Clock::new(15, 0));
+        assert_eq!(
+            b.ranges,
+            vec![
+                Range::new(Clock::new(9, 15), Clock::new(12, 10)),
+                Range::new(Clock::new(13, 0), Clock::new(15, 0))
+            ]
+        );
+        assert_eq!(b.lunches.len(), 1);
+        assert_eq!(b.lunches[0].1, LunchStep::Taken);
+        assert_eq!(b.break_time, Time::new(0, 10));
+        assert_eq!(b.rounded_work_time, r.rounded_work_time().unwrap());
+        assert!(b.trace().iter().any(|l| l.starts_with("開始 09:15")));
+
+        let r = record("LUC社員", "8:20", "12:00", "1:30");
+        let b = r.breakdown().unwrap();
+        assert!(b
+            .force_breaks
+            .iter()
+            .any(|(_, step)| *step != ForceBreakStep::Taken));
+        assert_eq!(b.break_time, r.break_time().unwrap());
+        assert!(b.trace().iter().any(|l| l.contains("休憩時間から")));
+    }
+
+    #[test]
+    fn audit() {
+        let r = record("LUC社員", "8:20", "19:40", "1:30");
+        let fields = r.export_audit_to_csv().unwrap();
+        assert_eq!(
+            fields[6..15].to_vec(),
+            vec![
+                "11.20",
+                "-00.10",
+                "-00.10",
+                "-00.50",
+                "-01.30",
+                "00.00",
+                "10:30-10:40：含める 00.00、15:00-15:15：含める 00.00、17:15-17:30：含める 00.00",
+                "-00.10",
+                "08.30"
+            ]
+        );
+
+        // 出勤から退勤まで＋各増減＝補正労働時間
+        let r = record("LUC社員", "8:40", "14:05", "1:30");
+        let fields = r.export_audit_to_csv().unwrap();
+        let sum: i32 = [6, 7, 8, 9, 10, 11, 13]
+            .iter()
+            .map(|i| {
+                let f = &fields[*i];
+                let m = f.trim_start_matches("-").replace(".", ":");
+                let m = m.parse::<Time>().unwrap().as_signed_minutes();
+                match f.starts_with("-") {
+                    true => -m,
+                    false => m,
+                }
+            })
+            .sum();
+        assert_eq!(sum, r.rounded_work_time().unwrap().as_signed_minutes());
+
+        // 休憩時間が足りず除けない強制休憩は 00.00 とする
+        let r = record("LUC社員", "8:20", "12:00", "0:10");
+        let fields = r.export_audit_to_csv().unwrap();
+        assert_eq!(fields[11], "00.10");
+        assert_eq!(
+            fields[12],
+            "10:30-10:40：含める 00.00、15:00-15:15：除く 00.10、17:15-17:30：除く 00.00"
+        );
+    }
+
+    #[test]
+    fn within_over_work_time() {
+        let r = record("LUC社員", "8:20", "19:40", "1:30");
+        assert_eq!(r.within_over_work_time().unwrap(), Time::new(0, 0));
+
+        // 所定労働時間5時間の区分
+        let (_, roster, calendar) =
+            setup_with(fixtures::rules_with_part_time_e("9:00", Some("15:00")), "E");
+        let r = Record::from_strs(
+            &roster, &calendar, "2026/10", "1", "10/01", "", "9:00", "19:00", "0:00", "", "", "1",
+        )
+        .unwrap();
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(9, 0));
+        assert_eq!(r.over_work_time().unwrap(), Time::new(1, 0));
+        assert_eq!(r.within_over_work_time().unwrap(), Time::new(3, 0));
+        assert_eq!(r.early_leave_time().unwrap(), Time::new(0, 0));
+    }
+
+    #[test]
+    fn tardiness() {
+        let r = record("LUC社員", "8:35", "17:20", "1:30");
+        assert_eq!(r.late_time().unwrap(), Time::new(0, 15));
+        assert_eq!(r.early_leave_time().unwrap(), Time::new(0, 45));
+
+        let r = record("LUC社員", "8:20", "18:10", "1:30");
+        assert_eq!(r.late_time().unwrap(), Time::new(0, 0));
+        assert_eq!(r.early_leave_time().unwrap(), Time::new(0, 0));
+
+        // 21:00からの5時間勤務の規定退勤時刻は翌2:00
+        let (_, roster, calendar) =
+            setup_with(fixtures::rules_with_part_time_e("21:00", None), "E");
+        let night = |left_at: &str| {
+            Record::from_strs(
+                &roster, &calendar, "2026/10", "1", "10/01", "", "21:00", left_at, "", "", "", "1",
+            )
+            .unwrap()
+        };
+        assert_eq!(night("翌1:00").early_leave_time().unwrap(), Time::new(1, 0));
+        assert_eq!(night("翌2:00").early_leave_time().unwrap(), Time::new(0, 0));
+    }
+
+    #[test]
+    fn late_night_work_time() {
+        let r = record("LUC社員", "8:30", "23:10", "1:30");
+        assert_eq!(r.late_night_work_time().unwrap(), Time::new(1, 0));
+        assert_eq!(
+            r.normal_work_time().unwrap().merge(&Time::new(1, 0)),
+            r.rounded_work_time().unwrap()
+        );
+
+        let r = record("LUC社員", "8:30", "17:30", "1:30");
+        assert_eq!(r.late_night_work_time().unwrap(), Time::new(0, 0));
+
+        let r = record("LUC社員", "8:30", "23:10", "1:30");
+        let over = r.over_work_time().unwrap();
+        assert_eq!(over, Time::new(4, 0));
+        assert_eq!(r.late_night_over_work_time(&over).unwrap(), Time::new(1, 0));
+    }
+
+    #[test]
+    fn weekly_over_work_time() {
+        let (rules, roster, calendar) = setup("A");
+
+        // 2026/10/04(日)〜10/09(金)、10/11(日)に7時間ずつ
+        let records: Vec<Record> = [
+            "10/04", "10/05", "10/06", "10/07", "10/08", "10/09", "10/11",
+        ]
+        .iter()
+        .map(|date| {
+            Record::from_strs(
+                &roster, &calendar, "2026/10", "1", date, "", "9:00", "17:10", "0:10", "", "", "1",
+            )
+            .unwrap()
+        })
+        .collect();
+        let records: Vec<&Record> = records.iter().collect();
+        assert_eq!(records[0].rounded_work_time().unwrap(), Time::new(7, 0));
+
+        let weekly = weekly_over_work_times(&records, &rules);
+        assert_eq!(weekly[4], Time::new(0, 0));
+        assert_eq!(weekly[5], Time::new(2, 0));
+        assert_eq!(weekly[6], Time::new(0, 0));
+    }
+
+    #[test]
+    fn calendar_date() {
+        let r = record("A", "9:00", "17:00", "0:10");
+        assert_eq!(
+            r.calendar_date().unwrap(),
+            NaiveDate::from_ymd_opt(2026, 10, 1).unwrap()
+        );
+
+        let (_, roster, calendar) = setup("A");
+        let r = Record::from_strs(
+            &roster, &calendar, "2027/01", "1", "12/26", "", "9:00", "17:00", "", "", "", "1",
+        )
+        .unwrap();
+        assert_eq!(
+            r.calendar_date().unwrap(),
+            NaiveDate::from_ymd_opt(2026, 12, 26).unwrap()
+        );
+        assert_eq!(r.day.peek().unwrap(), &DayKind::Sat);
+    }
+
+    #[test]
+    fn overnight() {
+        let r = record("A", "21:00", "6:00", "0:00");
+        assert_eq!(r.left_at.peek().unwrap().hours, 30);
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(9, 0));
+        assert_eq!(r.late_night_work_time().unwrap(), Time::new(7, 0));
+        assert_eq!(r.over_work_time().unwrap(), Time::new(1, 0));
+
+        let r = record("A", "21:00", "30:00", "0:00");
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(9, 0));
+
+        let r = record("A", "21:00", "翌6:00", "0:00");
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(9, 0));
+
+        let r = record("LUC社員", "8:30", "24:40", "1:30");
+        assert_eq!(r.left_at.peek().unwrap().hours, 24);
+        assert_eq!(r.rounded_work_time().unwrap(), Time::new(13, 30));
+        assert_eq!(r.late_night_work_time().unwrap(), Time::new(2, 30));
+    }
+
+    #[test]
+    fn review_records() {
+        let (rules, roster, _) = setup("A");
+        let mut diagnostics = Diagnostics::new();
+        let calendar =
+            calendar::collect_from_csv(Cursor::new("10/4,法定休日\n"), &rules, &mut diagnostics);
+
+        let records = collect_from_csv(
+            Cursor::new(
+                "年月,社員番号,氏名,日付,曜日,出勤時刻,退勤時刻,休憩時間,労働時間,備考,出勤日数
+                 2026/10,1,山田太郎,10/1,木,9:00,,1:00,,,1
+                 2026/10,1,山田太郎,10/2,金,9:00,17:00,1:00,7:00,,1
+                 2026/10,1,山田太郎,10/2,金,9:00,17:00,1:00,7:00,,1
+                 2026/10,1,山田太郎,11/2,月,9:00,17:00,1:00,7:00,,1
+                 2026/10,1,山田太郎,10/4,日,9:00,12:00,,3:00,,1
+                 2026/10,1,山田太郎,10/5,月,9:00,翌10:00,,,,1
+                 2026/10,1,山田太郎,10/6,火,9:00,10:00,2:00,,,1
+                 2026/10,1,山田太郎,10/7,水,,,,,,0
+                 2026/10,1,山田太郎,10/8,木,17:00,8:00,,,,1
+                 2026/10,1,山田太郎,10/9,金,21:00,翌6:00,,,,1
+",
+            ),
+            &roster,
+            &calendar,
+            &rules,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 10);
+        let found: Vec<u64> = diagnostics
+            .entries
+            .iter()
+            .filter(|d| d.severity == Severity::Review)
+            .map(|d| d.line)
+            .collect();
+        assert_eq!(found, vec![2, 4, 5, 6, 7, 8, 10]);
+        assert_eq!(diagnostics.review_count(), 7);
+        assert!(diagnostics.entries[0].reason.contains("退勤時刻"));
+        assert!(diagnostics.entries[6].reason.contains("翌日"));
+    }
+
+    #[test]
+    fn closing_day() {
+        let rules = include_str!("../設定.toml")
+            .replace("closing_day = 0", "closing_day = 25")
+            .parse::<Rules>()
+            .unwrap();
+        let (rules, roster, calendar) = setup_with(rules, "A");
+        let mut diagnostics = Diagnostics::new();
+
+        collect_from_csv(
+            Cursor::new(
+                "年月,社員番号,氏名,日付,曜日,出勤時刻,退勤時刻,休憩時間,労働時間,備考,出勤日数\n\
+                 2026/01,1,山田太郎,12/26,,9:00,17:00,1:00,7:00,,1\n\
+                 2026/01,1,山田太郎,1/25,,9:00,17:00,1:00,7:00,,1\n\
+                 2026/01,1,山田太郎,1/26,,9:00,17:00,1:00,7:00,,1\n",
+            ),
+            &roster,
+            &calendar,
+            &rules,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        let found: Vec<u64> = diagnostics.entries.iter().map(|d| d.line).collect();
+        assert_eq!(found, vec![4]);
+        assert!(diagnostics.entries[0]
+            .reason
+            .contains("2025/12/26〜2026/01/25"));
+    }
+
+    #[test]
+    fn rejected_lines() {
+        let (rules, roster, calendar) = setup("A");
+        let mut diagnostics = Diagnostics::new();
+
+        let records = collect_from_csv(
+            Cursor::new(
+                "年月,社員番号,氏名,日付,曜日,出勤時刻,退勤時刻,休憩時間,労働時間,備考,出勤日数\n\
+                 2026/10,1,山田太郎,10/1,金,9:00,17:00,1:00,7:00,,1\n\
+                 2026/10,2,佐藤花子,10/1,木,9:00,17:00,1:00,7:00,,1\n\
+                 \n\
+                 2026/10,1,山田太郎,10/2,金,9時,17:00,1:00,7:00,,1\n",
+            ),
+            &roster,
+            &calendar,
+            &rules,
+            &mut diagnostics,
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 1);
+        let found: Vec<(u64, Severity)> = diagnostics
+            .entries
+            .iter()
+            .map(|d| (d.line, d.severity.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, Severity::Warning),
+                (3, Severity::Rejected),
+                (5, Severity::Rejected)
+            ]
+        );
+        assert_eq!(
+            diagnostics.entries[1].raw,
+            "2026/10,2,佐藤花子,10/1,木,9:00,17:00,1:00,7:00,,1"
+        );
+        assert_eq!(diagnostics.rejected_count(), 2);
+    }
+}
@@ -0,0 +1,493 @@
+use crate::clock::{Clock, DateKind, DayKind, Range, Time};
+use crate::decode::InputEncoding;
+use crate::encode::{LineEnding, OutputEncoding};
+use crate::member::MemberKind;
+use anyhow::Context;
+use chrono::Weekday;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+
+const DEFAULT_RULES: &str = include_str!("../設定.toml");
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Schedule {
+    pub start_at: Clock,
+    pub lunch: Range,
+    pub force_breaks: Vec<Range>,
+    pub nominal_work_time: Time,
+    // 規定退勤時刻。無ければ規定出勤時刻から求める
+    pub explicit_end_at: Option<Clock>,
+}
+
+impl Schedule {
+    // 規定退勤時刻。指定が無ければ、規定出勤時刻から所定労働時間だけ勤務した時刻とし、
+    // その間にかかる昼休憩・強制休憩の分だけ後にする。日をまたげば24時以降の時刻になる
+    pub fn end_at(&self) -> Clock {
+        if let Some(end_at) = &self.explicit_end_at {
+            return end_at.clone();
+        }
+        let mut breaks: Vec<Range> = vec![];
+        for b in iter::once(&self.lunch).chain(self.force_breaks.iter()) {
+            breaks.push(b.clone());
+            breaks.push(b.next_day());
+        }
+        breaks.sort_by_key(|b| b.start.as_minutes());
+
+        let mut at = self.start_at.as_minutes();
+        let mut remaining = self.nominal_work_time.as_minutes();
+        for b in breaks.iter() {
+            let (start, end) = (b.start.as_minutes(), b.end.as_minutes());
+            if end <= at {
+                continue;
+            }
+            if start > at {
+                let work = remaining.min(start - at);
+                at += work;
+                remaining -= work;
+                if remaining == 0 {
+                    break;
+                }
+            }
+            at = end;
+        }
+        Clock::from_minutes(at + remaining)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSchedule {
+    start_at: String,
+    lunch: String,
+    force_breaks: Vec<String>,
+    nominal_work_time: String,
+    #[serde(default)]
+    end_at: Option<String>,
+}
+
+impl RawSchedule {
+    fn parse(&self) -> anyhow::Result<Schedule> {
+        Ok(Schedule {
+            start_at: self
+                .start_at
+                .parse()
+                .with_context(|| format!("Invalid start_at: {}", self.start_at))?,
+            lunch: self
+                .lunch
+                .parse()
+                .with_context(|| format!("Invalid lunch: {}", self.lunch))?,
+            force_breaks: self
+                .force_breaks
+                .iter()
+                .map(|b| {
+                    b.parse()
+                        .with_context(|| format!("Invalid force_breaks: {}", b))
+                })
+                .collect::<anyhow::Result<Vec<Range>>>()?,
+            nominal_work_time: self.nominal_work_time.parse().with_context(|| {
+                format!("Invalid nominal_work_time: {}", self.nominal_work_time)
+            })?,
+            explicit_end_at: match &self.end_at {
+                Some(e) => Some(self.parse_end_at(e)?),
+                None => None,
+            },
+        })
+    }
+
+    // 翌日の規定退勤時刻は「翌1:00」か「25:00」と書く。規定出勤時刻より後でなければエラーにする
+    fn parse_end_at(&self, end_at: &str) -> anyhow::Result<Clock> {
+        let parsed =
+            Clock::parse_extended(end_at).with_context(|| format!("Invalid end_at: {}", end_at))?;
+        let start_at: Clock = self.start_at.parse()?;
+        if !parsed.later_than(&start_at) {
+            return Err(anyhow!(
+                "end_at {} must be after start_at {} (write 翌 or 24:00 and later for the next day)",
+                end_at,
+                self.start_at
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOvertime {
+    week_start: String,
+    weekly_work_time: String,
+}
+
+impl Default for RawOvertime {
+    fn default() -> Self {
+        Self {
+            week_start: "日".to_string(),
+            weekly_work_time: "40:00".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCalendar {
+    national_holidays: bool,
+    #[serde(default)]
+    statutory_offs: Vec<String>,
+    #[serde(default)]
+    company_offs: Vec<String>,
+    #[serde(default)]
+    closing_day: u8,
+}
+
+impl Default for RawCalendar {
+    fn default() -> Self {
+        Self {
+            national_holidays: true,
+            statutory_offs: vec![],
+            company_offs: vec![],
+            closing_day: 0,
+        }
+    }
+}
+
+fn parse_weekday(s: &str) -> anyhow::Result<Weekday> {
+    s.parse::<DayKind>()?
+        .weekday()
+        .ok_or(anyhow!("Invalid weekday: {}", s))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDiagnostics {
+    strict: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+struct RawEncoding {
+    input: String,
+    output: String,
+    line_ending: String,
+}
+
+impl Default for RawEncoding {
+    fn default() -> Self {
+        Self {
+            input: "auto".to_string(),
+            output: "shift_jis".to_string(),
+            line_ending: "lf".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+struct RawReconciliation {
+    work_time: String,
+    work_days: u8,
+}
+
+impl Default for RawReconciliation {
+    fn default() -> Self {
+        Self {
+            work_time: "0:30".to_string(),
+            work_days: 1,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+struct RawTotals {
+    overwrite_days: bool,
+    leave_remarks: Vec<String>,
+}
+
+impl Default for RawTotals {
+    fn default() -> Self {
+        Self {
+            overwrite_days: false,
+            leave_remarks: ["有休", "有給", "特休", "代休", "振休"]
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRules {
+    #[serde(default)]
+    overtime: RawOvertime,
+    #[serde(default)]
+    calendar: RawCalendar,
+    #[serde(default)]
+    diagnostics: RawDiagnostics,
+    #[serde(default)]
+    encoding: RawEncoding,
+    #[serde(default)]
+    reconciliation: RawReconciliation,
+    #[serde(default)]
+    totals: RawTotals,
+    kinds: HashMap<String, RawSchedule>,
+    labels: HashMap<String, String>,
+    #[serde(default)]
+    columns: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rules {
+    pub week_start: Weekday,
+    pub weekly_work_time: Time,
+    pub national_holidays: bool,
+    pub weekly_offs: Vec<(Weekday, DateKind)>,
+    // 締め日。0は末日締め
+    pub closing_day: u8,
+    pub strict: bool,
+    pub input_encoding: InputEncoding,
+    pub output_encoding: OutputEncoding,
+    pub line_ending: LineEnding,
+    // 照合.csvで差ありとする差の大きさ
+    pub reconcile_work_time: Time,
+    pub reconcile_work_days: u8,
+    pub overwrite_days: bool,
+    // 打刻の無い出勤日でも、備考にこれらを含めば事故欠勤日数に数えない
+    pub leave_remarks: Vec<String>,
+    schedules: HashMap<MemberKind, Schedule>,
+    labels: HashMap<String, MemberKind>,
+    columns: HashMap<String, Vec<String>>,
+}
+
+impl Rules {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Self::default_rules();
+        }
+
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        text.parse()
+            .with_context(|| format!("Malformed rules file: {}", path.display()))
+    }
+
+    pub fn default_rules() -> anyhow::Result<Self> {
+        DEFAULT_RULES.parse()
+    }
+
+    pub fn kind_of(&self, label: &str) -> anyhow::Result<MemberKind> {
+        self.labels
+            .get(label.trim())
+            .cloned()
+            .ok_or(anyhow!("Unknown member kind label: {}", label))
+    }
+
+    pub fn schedule(&self, kind: &MemberKind) -> anyhow::Result<&Schedule> {
+        self.schedules
+            .get(kind)
+            .ok_or(anyhow!("No schedule has been defined for {}", kind))
+    }
+
+    // 列の見出しと、その別名
+    pub fn column_names<'a>(&'a self, heading: &'a str) -> Vec<&'a str> {
+        let mut names = vec![heading];
+        if let Some(aliases) = self.columns.get(heading) {
+            names.extend(aliases.iter().map(|a| a.as_str()));
+        }
+        names
+    }
+}
+
+impl FromStr for Rules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw: RawRules = toml::from_str(s)?;
+        let week_start = parse_weekday(&raw.overtime.week_start)
+            .with_context(|| format!("Invalid week_start: {}", raw.overtime.week_start))?;
+        let weekly_work_time = raw.overtime.weekly_work_time.parse().with_context(|| {
+            format!(
+                "Invalid weekly_work_time: {}",
+                raw.overtime.weekly_work_time
+            )
+        })?;
+        let mut weekly_offs = vec![];
+        for (days, kind) in [
+            (&raw.calendar.statutory_offs, DateKind::StatutoryOff),
+            (&raw.calendar.company_offs, DateKind::CompanyOff),
+        ] {
+            for day in days.iter() {
+                let weekday = parse_weekday(day)?;
+                if weekly_offs.iter().any(|(w, _)| *w == weekday) {
+                    return Err(anyhow!("Weekday {} is listed more than once", day));
+                }
+                weekly_offs.push((weekday, kind.clone()));
+            }
+        }
+
+        if raw.calendar.closing_day > 28 {
+            return Err(anyhow!(
+                "Invalid closing_day: {} (0 for the end of month, or 1-28)",
+                raw.calendar.closing_day
+            ));
+        }
+
+        let mut schedules = HashMap::new();
+
+        for (key, raw_schedule) in raw.kinds.iter() {
+            let schedule = raw_schedule
+                .parse()
+                .with_context(|| format!("In [kinds.{}]", key))?;
+            schedules.insert(MemberKind::new(key.as_str()), schedule);
+        }
+
+        let mut labels = HashMap::new();
+        for (label, key) in raw.labels.iter() {
+            let kind = MemberKind::new(key.as_str());
+            if !schedules.contains_key(&kind) {
+                return Err(anyhow!(
+                    "Label {} refers to {}, but [kinds.{}] is missing",
+                    label,
+                    key,
+                    key
+                ));
+            }
+            labels.insert(label.to_string(), kind);
+        }
+
+        Ok(Self {
+            week_start,
+            weekly_work_time,
+            national_holidays: raw.calendar.national_holidays,
+            weekly_offs,
+            closing_day: raw.calendar.closing_day,
+            strict: raw.diagnostics.strict,
+            input_encoding: raw.encoding.input.parse()?,
+            output_encoding: raw.encoding.output.parse()?,
+            line_ending: raw.encoding.line_ending.parse()?,
+            reconcile_work_time: raw
+                .reconciliation
+                .work_time
+                .parse()
+                .with_context(|| format!("Invalid work_time: {}", raw.reconciliation.work_time))?,
+            reconcile_work_days: raw.reconciliation.work_days,
+            overwrite_days: raw.totals.overwrite_days,
+            leave_remarks: raw.totals.leave_remarks,
+            schedules,
+            labels,
+            columns: raw.columns,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, Time};
+    use crate::fixtures;
+    use crate::member::MemberKind;
+    use crate::rules::Rules;
+
+    #[test]
+    fn default_rules() {
+        let rules = Rules::default_rules().unwrap();
+
+        let full_time = rules.schedule(&rules.kind_of("LUC社員").unwrap()).unwrap();
+        assert_eq!(full_time.start_at, Clock::new(8, 30));
+        assert_eq!(full_time.force_breaks.len(), 3);
+        assert_eq!(full_time.lunch.start, Clock::new(12, 10));
+        assert_eq!(full_time.lunch.end, Clock::new(13, 0));
+        assert_eq!(full_time.nominal_work_time, Time::new(8, 0));
+        assert_eq!(full_time.end_at(), Clock::new(18, 0));
+
+        // 午後からの勤務では、勤務前の昼休憩・強制休憩は足さない
+        let mut afternoon = full_time.clone();
+        afternoon.start_at = Clock::new(13, 0);
+        assert_eq!(afternoon.end_at(), Clock::new(21, 30));
+
+        let part_time = rules.schedule(&rules.kind_of("A").unwrap()).unwrap();
+        assert_eq!(part_time.start_at, Clock::new(9, 0));
+        assert_eq!(part_time.force_breaks.len(), 1);
+
+        assert_eq!(rules.kind_of("役員").unwrap(), MemberKind::new("FullTime"));
+        assert!(rules.kind_of("E").is_err());
+    }
+
+    #[test]
+    fn custom_kinds() {
+        let rules = fixtures::rules_with_part_time_e("10:00", None);
+        let kind = rules.kind_of("E").unwrap();
+        assert_eq!(kind, MemberKind::new("PartTimeE"));
+        assert_eq!(rules.schedule(&kind).unwrap().start_at, Clock::new(10, 0));
+        assert_eq!(rules.schedule(&kind).unwrap().end_at(), Clock::new(15, 50));
+
+        let rules = fixtures::rules_with_part_time_e("14:00", None);
+        let kind = rules.kind_of("E").unwrap();
+        assert_eq!(rules.schedule(&kind).unwrap().end_at(), Clock::new(19, 0));
+
+        // 日をまたぐ勤務は24時以降の時刻にする
+        let rules = fixtures::rules_with_part_time_e("21:00", None);
+        let kind = rules.kind_of("E").unwrap();
+        assert_eq!(rules.schedule(&kind).unwrap().end_at(), Clock::new(26, 0));
+
+        // 翌日の規定退勤時刻は「翌」か24時以降で書き、それ以外の出勤時刻以前の時刻は受け付けない
+        for (end_at, expected) in [
+            ("24:30", Clock::new(0, 30).next_day()),
+            ("翌1:00", Clock::new(25, 0)),
+        ] {
+            let rules = fixtures::rules_with_part_time_e("21:00", Some(end_at));
+            let kind = rules.kind_of("E").unwrap();
+            assert_eq!(rules.schedule(&kind).unwrap().end_at(), expected);
+        }
+        assert!(fixtures::part_time_e_text("21:00", Some("1:00"))
+            .parse::<Rules>()
+            .is_err());
+
+        let rules = fixtures::rules_with_part_time_e("10:00", Some("15:30"));
+        let kind = rules.kind_of("E").unwrap();
+        assert_eq!(rules.schedule(&kind).unwrap().end_at(), Clock::new(15, 30));
+    }
+
+    #[test]
+    fn malformed_rules() {
+        let text = include_str!("../設定.toml");
+
+        assert!(text
+            .replace("start_at = \"8:30\"", "start_at = \"8時30分\"")
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace("\"A\" = \"PartTimeA\"", "\"A\" = \"PartTimeZ\"")
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace("lunch = \"12:10-13:00\"", "lunch = \"13:00-12:10\"")
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace("week_start = \"日\"", "week_start = \"Sunday\"")
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace("closing_day = 0", "closing_day = 31")
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace(
+                "statutory_offs = []\ncompany_offs = []",
+                "statutory_offs = [\"日\"]\ncompany_offs = [\"日\"]"
+            )
+            .parse::<Rules>()
+            .is_err());
+        assert!(text
+            .replace("input = \"auto\"", "input = \"euc-jp\"")
+            .parse::<Rules>()
+            .is_err());
+        assert!("[kinds.FullTime]".parse::<Rules>().is_err());
+    }
+}
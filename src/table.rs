@@ -0,0 +1,204 @@
+use crate::diagnostics::Diagnostics;
+use crate::rules::Rules;
+use csv::{QuoteStyle, ReaderBuilder, StringRecord, Terminator, WriterBuilder};
+use std::collections::HashMap;
+use std::io::Read;
+
+// CSVの1行。line は元ファイルでの行番号、raw は元の文字列
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub line: u64,
+    pub raw: String,
+    pub fields: Vec<String>,
+}
+
+impl Row {
+    pub fn get(&self, n: usize) -> &str {
+        self.fields.get(n).map_or("", |f| f.as_str())
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.fields.iter().all(|f| f.trim().is_empty())
+    }
+}
+
+// RFC 4180 に従って読む（引用符で囲んだ項目、項目内のカンマと改行、"" による引用符、CRLF）
+pub fn read_rows<R: Read>(mut reader: R, name: &str, diagnostics: &mut Diagnostics) -> Vec<Row> {
+    let mut buf = vec![];
+    if let Err(e) = reader.read_to_end(&mut buf) {
+        let row = Row {
+            line: 0,
+            raw: "".to_string(),
+            fields: vec![],
+        };
+        diagnostics.reject(name, &row, &e.into());
+        return vec![];
+    }
+    // 行番号は項目内の改行も数えたいので、読み込んだ位置から求める
+    // （CRLFの行では位置がLFの手前を指すので、LFまで含めて数える）
+    let line_at = |byte: u64| {
+        let mut end = byte as usize;
+        if buf.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+        buf[..end].iter().filter(|b| **b == b'\n').count() as u64 + 1
+    };
+    let raw_between = |start: u64, end: u64| {
+        String::from_utf8_lossy(&buf[start as usize..end as usize])
+            .trim_matches(|c| c == '\r' || c == '\n')
+            .to_string()
+    };
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(buf.as_slice());
+    let mut record = StringRecord::new();
+    let mut rows = vec![];
+    loop {
+        let start = reader.position().byte();
+        let result = reader.read_record(&mut record);
+        let row = Row {
+            line: line_at(start),
+            raw: raw_between(start, reader.position().byte()),
+            fields: record.iter().map(|f| f.to_string()).collect(),
+        };
+        match result {
+            Ok(true) => rows.push(row),
+            Ok(false) => break,
+            Err(e) => diagnostics.reject(name, &row, &e.into()),
+        }
+    }
+    rows
+}
+
+// 見出し行から探した列の位置
+#[derive(Debug, Clone, Default)]
+pub struct Columns {
+    positions: HashMap<String, usize>,
+}
+
+impl Columns {
+    // required が1つでも見つからなければエラーにする。optional は無くてもよい
+    pub fn locate(
+        headings: &Row,
+        required: &[&str],
+        optional: &[&str],
+        rules: &Rules,
+        name: &str,
+    ) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = headings
+            .fields
+            .iter()
+            .map(|f| f.trim_start_matches('\u{feff}').trim())
+            .collect();
+        let mut positions = HashMap::new();
+        let mut missing = vec![];
+
+        for heading in required.iter().chain(optional.iter()) {
+            let names = rules.column_names(heading);
+            match fields.iter().position(|f| names.contains(f)) {
+                Some(i) => {
+                    positions.insert(heading.to_string(), i);
+                }
+                None if required.contains(heading) => missing.push(names.join("/")),
+                None => {}
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Missing columns in {} (line {}): {}",
+                name,
+                headings.line,
+                missing.join(", ")
+            ));
+        }
+        Ok(Self { positions })
+    }
+
+    pub fn get<'a>(&self, row: &'a Row, heading: &str) -> &'a str {
+        self.positions.get(heading).map_or("", |i| row.get(*i))
+    }
+
+    pub fn position(&self, heading: &str) -> Option<usize> {
+        self.positions.get(heading).copied()
+    }
+
+    pub fn contains(&self, position: usize) -> bool {
+        self.positions.values().any(|i| *i == position)
+    }
+}
+
+// カンマ・引用符・改行を含む項目だけを引用符で囲んで1行にする（改行は付けない）
+pub fn to_line<S: AsRef<str>>(fields: &[S]) -> anyhow::Result<String> {
+    let mut writer = WriterBuilder::new()
+        .quote_style(QuoteStyle::Necessary)
+        .terminator(Terminator::Any(b'\n'))
+        .from_writer(vec![]);
+    writer.write_record(fields.iter().map(|f| f.as_ref()))?;
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| anyhow!("Failed to write a CSV line: {}", e))?;
+    let line = String::from_utf8(bytes)?;
+    Ok(line.trim_end_matches('\n').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::Diagnostics;
+    use crate::rules::Rules;
+    use crate::table::{read_rows, to_line, Columns};
+    use std::io::Cursor;
+
+    #[test]
+    fn quoted() {
+        let rows = read_rows(
+            Cursor::new("1,\"早退, 通院\",\"a \"\"b\"\"\"\r\n2,\"複数\n行\"\r\n3\n"),
+            "test",
+            &mut Diagnostics::new(),
+        );
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].fields, vec!["1", "早退, 通院", "a \"b\""]);
+        assert_eq!(rows[1].get(1), "複数\n行");
+        assert_eq!(rows[1].raw, "2,\"複数\n行\"");
+        assert_eq!(
+            rows.iter().map(|r| r.line).collect::<Vec<u64>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(rows[2].get(5), "");
+
+        assert_eq!(
+            to_line(&["1", "早退, 通院", "a \"b\"", ""]).unwrap(),
+            "1,\"早退, 通院\",\"a \"\"b\"\"\","
+        );
+    }
+
+    #[test]
+    fn columns() {
+        let rules = include_str!("../設定.toml")
+            .replace("\n[columns]\n", "\n[columns]\n\"出勤時刻\" = [\"始業\"]\n")
+            .parse::<Rules>()
+            .unwrap();
+        let rows = read_rows(
+            Cursor::new("\u{feff}社員番号,氏名,始業\n1,山田太郎,8:30\n"),
+            "test",
+            &mut Diagnostics::new(),
+        );
+
+        let columns = Columns::locate(
+            &rows[0],
+            &["社員番号", "出勤時刻"],
+            &["備考"],
+            &rules,
+            "test",
+        )
+        .unwrap();
+        assert_eq!(columns.get(&rows[1], "社員番号"), "1");
+        assert_eq!(columns.get(&rows[1], "出勤時刻"), "8:30");
+        assert_eq!(columns.get(&rows[1], "備考"), "");
+        assert!(!columns.contains(1));
+
+        assert!(Columns::locate(&rows[0], &["退勤時刻"], &[], &rules, "test").is_err());
+    }
+}
@@ -1,23 +1,85 @@
 use crate::cell::Cell;
-use crate::clock::Time;
+use crate::clock::{DateKind, Time};
+use crate::diagnostics::Diagnostics;
 use crate::member::Member;
-use crate::record::Record;
-use std::collections::HashSet;
-use std::io::BufRead;
+use crate::record::{self, DayCounts, Record};
+use crate::rules::Rules;
+use crate::table::{read_rows, Columns, Row};
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+pub const FILE_NAME: &str = "PCA給与X.csv";
+
+// 月の法定外労働時間の区切り（45時間以下・45時間超・60時間超）
+const TIERS: [(u16, &str); 3] = [(45 * 60, "45下"), (60 * 60, "45超"), (u16::MAX, "60超")];
+
+const OVERTIME_HEADINGS: [&str; 6] = [
+    "残業平日普通",
+    "残業平日深夜",
+    "残業休日普通",
+    "残業休日深夜",
+    "残業法定普通",
+    "残業法定深夜",
+];
+
+// 見出しで探した列以外の列。見出しは入力のまま出力し、補正する列は見出しで探す
+#[derive(Debug, Clone, Default)]
+pub struct OtherColumns {
+    pub headings: Vec<String>,
+    columns: Columns,
+}
+
+impl OtherColumns {
+    // 補正して書き込む列が1つでも無ければエラーにする
+    pub fn locate(headings: Row, rules: &Rules) -> anyhow::Result<Self> {
+        let mut expected = corrected_headings();
+        expected.extend(
+            ["遅刻早退回数", "休日出勤日数", "事故欠勤日数"]
+                .iter()
+                .map(|h| h.to_string()),
+        );
+        let expected: Vec<&str> = expected.iter().map(|h| h.as_str()).collect();
+        let columns = Columns::locate(&headings, &expected, &[], rules, FILE_NAME)?;
+        Ok(Self {
+            headings: headings
+                .fields
+                .iter()
+                .map(|f| f.trim_start_matches('\u{feff}').trim().to_string())
+                .collect(),
+            columns,
+        })
+    }
+
+    fn position(&self, heading: &str) -> Option<usize> {
+        self.columns.position(heading)
+    }
+}
 
 #[derive(Debug)]
 pub struct Total {
     pub member: Cell<Member>,
-    pub nominal_work_days: Cell<u8>,
+    // 日数は半日（0.5）などの小数もそのまま読み書きする
+    pub nominal_work_days: Cell<f32>,
     pub nominal_work_time: Cell<Time>,
-    pub work_days: Cell<u8>,
+    pub work_days: Cell<f32>,
     pub total_work_time: Cell<Time>,
     pub others: Vec<String>,
+    pub other_columns: OtherColumns,
     pub rounded_work_time: Cell<Time>,
     pub rounded_over_work_time: Cell<Time>,
+    pub rounded_within_over_work_time: Cell<Time>,
+    // 残業かどうかを問わない深夜労働時間の合計。残業*深夜の列には残業の分だけを書く
+    pub rounded_late_night_work_time: Cell<Time>,
+    pub corrected_times: HashMap<String, Time>,
+    // 出勤簿から数えた日数。overwrite_days なら出勤日数・休日出勤日数・事故欠勤日数を上書きする
+    pub counted_days: Cell<DayCounts>,
+    pub overwrite_days: bool,
+    pub tardiness_count: Cell<u16>,
 }
 
 impl Total {
+    #[allow(clippy::too_many_arguments)]
     pub fn from_strs(
         roster: &HashSet<Member>,
         member_id: &str,
@@ -26,23 +88,36 @@ impl Total {
         work_days: &str,
         total_work_time: &str,
         others: Vec<&str>,
+        other_columns: &OtherColumns,
     ) -> anyhow::Result<Self> {
-        let member_id: u16 = member_id.parse()?;
+        let member_id: u16 = member_id
+            .parse()
+            .with_context(|| format!("Invalid member id: {}", member_id))?;
         let member = roster
             .iter()
             .find(|m| m.id == member_id)
-            .ok_or(anyhow!("No member has been found"))?
+            .ok_or(anyhow!("No member has been found: {}", member_id))?
             .to_owned();
 
         Ok(Self {
             member: Cell::new(member),
-            nominal_work_days: nominal_work_days.parse()?,
-            nominal_work_time: nominal_work_time.parse()?,
-            work_days: work_days.parse()?,
-            total_work_time: total_work_time.parse()?,
+            nominal_work_days: Cell::from_field(nominal_work_days).context("要勤務日数")?,
+            nominal_work_time: Cell::from_field(nominal_work_time).context("要勤務時間")?,
+            work_days: Cell::from_field(work_days).context("出勤日数")?,
+            total_work_time: Cell::from_field(total_work_time).context("出勤時間")?,
             others: others.iter().map(|o| o.to_string()).collect(),
+            other_columns: other_columns.clone(),
             rounded_work_time: Cell::new(Time::new(0, 0)),
             rounded_over_work_time: Cell::new(Time::new(0, 0)),
+            rounded_within_over_work_time: Cell::new(Time::new(0, 0)),
+            rounded_late_night_work_time: Cell::new(Time::new(0, 0)),
+            corrected_times: corrected_headings()
+                .into_iter()
+                .map(|h| (h, Time::new(0, 0)))
+                .collect(),
+            counted_days: Cell::NoData,
+            overwrite_days: false,
+            tardiness_count: Cell::new(0),
         })
     }
 
@@ -54,24 +129,121 @@ impl Total {
             work_days: Cell::NoData,
             total_work_time: Cell::NoData,
             others: vec![],
+            other_columns: OtherColumns::default(),
             rounded_work_time: Cell::NoData,
             rounded_over_work_time: Cell::NoData,
+            rounded_within_over_work_time: Cell::NoData,
+            rounded_late_night_work_time: Cell::NoData,
+            corrected_times: HashMap::new(),
+            counted_days: Cell::NoData,
+            overwrite_days: false,
+            tardiness_count: Cell::NoData,
         }
     }
 
-    pub fn total(mut self, records: Vec<&Record>) -> anyhow::Result<Self> {
-        for r in records.iter() {
+    pub fn total(mut self, mut records: Vec<&Record>, rules: &Rules) -> anyhow::Result<Self> {
+        records.sort_by_key(|r| r.calendar_date().ok());
+        let weekly = record::weekly_over_work_times(&records, rules);
+        self.counted_days = Cell::new(record::count_days(&records, rules));
+        self.overwrite_days = rules.overwrite_days;
+        let mut accumulated = Time::new(0, 0);
+
+        for (r, w) in records.iter().zip(weekly.iter()) {
+            let over = r.over_work_time().unwrap_or(Time::new(0, 0)).merge(w);
             self.rounded_work_time = self
                 .rounded_work_time
                 .map(|s| s.merge(&r.rounded_work_time().unwrap_or(Time::new(0, 0))));
-            self.rounded_over_work_time = self
-                .rounded_over_work_time
-                .map(|s| s.merge(&r.over_work_time().unwrap_or(Time::new(0, 0))));
+            self.rounded_over_work_time = self.rounded_over_work_time.map(|s| s.merge(&over));
+            // 週の法定労働時間を超えて法定外になった分は、法定内残業から除く
+            let within = r.within_over_work_time().unwrap_or(Time::new(0, 0)).sub(w);
+            self.rounded_within_over_work_time =
+                self.rounded_within_over_work_time.map(|s| s.merge(&within));
+
+            // 遅刻と早退は1回ずつ数える
+            for t in [r.late_time(), r.early_leave_time()] {
+                let t = t.unwrap_or(Time::new(0, 0));
+                if t.as_minutes() > 0 {
+                    self.tardiness_count = self.tardiness_count.map(|c| c + 1);
+                    self.add_time("遅刻早退時間", &t);
+                }
+            }
+
+            let late_night_work_time = r.late_night_work_time().unwrap_or(Time::new(0, 0));
+            self.rounded_late_night_work_time = self
+                .rounded_late_night_work_time
+                .map(|s| s.merge(&late_night_work_time));
+            let (normal, late_night) = match r.date.peek()?.date_type {
+                DateKind::On => ("残業平日普通", "残業平日深夜"),
+                DateKind::CompanyOff => ("残業休日普通", "残業休日深夜"),
+                // 法定休日の労働は全て休日労働とし、45時間・60時間の累計には含めない
+                DateKind::StatutoryOff => {
+                    self.add_time("残業法定深夜", &late_night_work_time);
+                    self.add_time(
+                        "残業法定普通",
+                        &r.normal_work_time().unwrap_or(Time::new(0, 0)),
+                    );
+                    continue;
+                }
+                DateKind::Unknown => continue,
+            };
+            let late_night_over = r
+                .late_night_over_work_time(&over)
+                .unwrap_or(Time::new(0, 0));
+            let normal_over = over.sub(&late_night_over);
+            self.add_time(normal, &normal_over);
+            self.add_time(late_night, &late_night_over);
+            for (heading, time) in [(normal, normal_over), (late_night, late_night_over)].iter() {
+                for (tier, t) in split_by_tiers(&accumulated, time) {
+                    self.add_time(&format!("{}{}", heading, tier), &t);
+                }
+                accumulated = accumulated.merge(time);
+            }
         }
 
         Ok(self)
     }
 
+    fn add_time(&mut self, heading: &str, time: &Time) {
+        let sum = self
+            .corrected_times
+            .remove(heading)
+            .unwrap_or(Time::new(0, 0))
+            .merge(time);
+        self.corrected_times.insert(heading.to_string(), sum);
+    }
+
+    // PCA給与Xから読み込んだままのその他の列の時間。空欄は0とする
+    pub fn original_time(&self, heading: &str) -> Time {
+        self.other_columns
+            .position(heading)
+            .and_then(|i| self.others.get(i))
+            .and_then(|v| v.trim().parse::<Time>().ok())
+            .unwrap_or(Time::new(0, 0))
+    }
+
+    // PCA給与Xから読み込んだままのその他の列の日数
+    pub fn original_days(&self, heading: &str) -> Cell<f32> {
+        self.other_columns
+            .position(heading)
+            .and_then(|i| self.others.get(i))
+            .map_or(Cell::NoData, |v| v.trim().parse().unwrap_or(Cell::NoData))
+    }
+
+    // 残業時間の合計（45時間・60時間の内訳を除く）
+    pub fn original_over_work_time(&self) -> Time {
+        OVERTIME_HEADINGS
+            .iter()
+            .fold(Time::new(0, 0), |sum, h| sum.merge(&self.original_time(h)))
+    }
+
+    // 補正した残業時間の合計。残業*深夜の列は法定外労働時間のうち深夜の分だけなので、
+    // 深夜の普通の労働時間は含まない
+    pub fn corrected_over_work_time(&self) -> Time {
+        OVERTIME_HEADINGS.iter().fold(Time::new(0, 0), |sum, h| {
+            sum.merge(self.corrected_times.get(*h).unwrap_or(&Time::new(0, 0)))
+        })
+    }
+
     pub fn diff_work_time(&self) -> anyhow::Result<Time> {
         let diff = self
             .rounded_work_time
@@ -82,19 +254,45 @@ impl Total {
         Ok(diff)
     }
 
-    pub fn export_to_csv(&self) -> String {
-        let mut buf: Vec<String> = vec![];
-        buf.push(self.member.to_string());
-        buf.push(self.nominal_work_days.to_string());
-        buf.push(self.nominal_work_time.to_string());
-        buf.push(self.work_days.to_string());
-        buf.push(self.total_work_time.to_string());
-        buf.push(self.rounded_work_time.to_string());
-        buf.push(self.diff_work_time().unwrap_or(Time::new(0, 0)).to_string());
-        buf.push(self.rounded_over_work_time.to_string());
+    pub fn export_to_csv(&self) -> Vec<String> {
+        let mut buf: Vec<String> = match self.member.peek() {
+            Ok(m) => m.to_fields(),
+            Err(_) => vec!["".to_string(); 2],
+        };
+        let counted = match self.overwrite_days {
+            true => self.counted_days.peek().ok(),
+            false => None,
+        };
+        buf.append(&mut vec![
+            self.nominal_work_days.to_string(),
+            self.nominal_work_time.to_string(),
+            counted.map_or(self.work_days.to_string(), |c| c.work_days.to_string()),
+            self.total_work_time.to_string(),
+            self.rounded_work_time.to_string(),
+            self.diff_work_time().unwrap_or(Time::new(0, 0)).to_string(),
+            self.rounded_over_work_time.to_string(),
+            self.rounded_within_over_work_time.to_string(),
+            self.rounded_late_night_work_time.to_string(),
+        ]);
+        // 短い行も見出しの数まで空欄で埋める
+        let mut others = self.others.clone();
+        others.resize(
+            others.len().max(self.other_columns.headings.len()),
+            "".to_string(),
+        );
+        for (heading, time) in self.corrected_times.iter() {
+            self.set_other(&mut others, heading, time.to_string());
+        }
+        if let Ok(c) = self.tardiness_count.peek() {
+            self.set_other(&mut others, "遅刻早退回数", c.to_string());
+        }
+        if let Some(c) = counted {
+            self.set_other(&mut others, "休日出勤日数", c.holiday_work_days.to_string());
+            self.set_other(&mut others, "事故欠勤日数", c.absence_days.to_string());
+        }
+
         buf.append(
-            &mut self
-                .others
+            &mut others
                 .iter()
                 .map(|value| {
                     if value.contains(":") {
@@ -107,32 +305,271 @@ impl Total {
                 })
                 .collect::<Vec<String>>(),
         );
-        buf.join(",")
+        buf
+    }
+
+    fn set_other(&self, others: &mut [String], heading: &str, value: String) {
+        if let Some(i) = self.other_columns.position(heading) {
+            others[i] = value;
+        }
     }
 }
 
-pub fn collect_from_csv<R: BufRead>(reader: R, roster: &HashSet<Member>) -> Vec<Total> {
-    reader
-        .lines()
-        .flat_map(|line| {
-            line.ok().and_then(|l| {
-                let trimmed = l.replace("\"", "");
-                let mut columns = trimmed.split(",");
-                Total::from_strs(
-                    roster,
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.next().unwrap_or(""),
-                    columns.collect(),
-                )
-                .ok()
-            })
+// 補正した値で上書きする列
+fn corrected_headings() -> Vec<String> {
+    let mut headings = vec![
+        "残業法定普通".to_string(),
+        "残業法定深夜".to_string(),
+        "遅刻早退時間".to_string(),
+    ];
+    for heading in [
+        "残業平日普通",
+        "残業平日深夜",
+        "残業休日普通",
+        "残業休日深夜",
+    ]
+    .iter()
+    {
+        headings.push(heading.to_string());
+        for (_, tier) in TIERS.iter() {
+            headings.push(format!("{}{}", heading, tier));
+        }
+    }
+    headings
+}
+
+fn split_by_tiers(accumulated: &Time, time: &Time) -> Vec<(&'static str, Time)> {
+    let start = accumulated.as_minutes();
+    let end = start + time.as_minutes();
+    let mut lower = 0;
+
+    TIERS
+        .iter()
+        .map(|(upper, tier)| {
+            let from = start.max(lower);
+            let to = end.min(*upper);
+            lower = *upper;
+            match to > from {
+                true => (*tier, Time::new(0, to - from)),
+                false => (*tier, Time::new(0, 0)),
+            }
         })
         .collect()
 }
 
+pub fn collect_from_csv<R: Read>(
+    reader: R,
+    roster: &HashSet<Member>,
+    rules: &Rules,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<Vec<Total>> {
+    let rows = read_rows(reader, FILE_NAME, diagnostics);
+    let headings = rows
+        .first()
+        .ok_or(anyhow!("{} has no heading row", FILE_NAME))?;
+    let columns = Columns::locate(
+        headings,
+        &[
+            "社員コード",
+            "要勤務日数",
+            "要勤務時間",
+            "出勤日数",
+            "出勤時間",
+        ],
+        &["氏名"],
+        rules,
+        FILE_NAME,
+    )?;
+    let other_columns = OtherColumns::locate(others_of(headings, &columns), rules)?;
+
+    let mut totals = vec![];
+    for row in rows.iter().skip(1).filter(|r| !r.is_blank()) {
+        // 見出しで探した列以外は、並び順のまま出力する
+        let others = others_of(row, &columns).fields;
+        let others = others.iter().map(|f| f.as_str()).collect();
+        let result = Total::from_strs(
+            roster,
+            columns.get(row, "社員コード"),
+            columns.get(row, "要勤務日数"),
+            columns.get(row, "要勤務時間"),
+            columns.get(row, "出勤日数"),
+            columns.get(row, "出勤時間"),
+            others,
+            &other_columns,
+        );
+        match result {
+            Ok(total) => totals.push(total),
+            Err(e) => diagnostics.reject(FILE_NAME, row, &e),
+        }
+    }
+    Ok(totals)
+}
+
+// 見出しで探した列を除いた行
+fn others_of(row: &Row, columns: &Columns) -> Row {
+    Row {
+        line: row.line,
+        raw: row.raw.clone(),
+        fields: row
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !columns.contains(*i))
+            .map(|(_, f)| f.to_string())
+            .collect(),
+    }
+}
+
+// 出力の見出し。補正した列の後に、入力のその他の列の見出しを並べる
+pub fn csv_headings(totals: &[Total]) -> Vec<String> {
+    let headings: Vec<String> = get_csv_headings()
+        .split(",")
+        .map(|h| h.to_string())
+        .collect();
+    let others = totals
+        .iter()
+        .map(|t| &t.other_columns.headings)
+        .find(|h| !h.is_empty());
+    match others {
+        Some(others) => {
+            let offset = headings.iter().position(|h| h == "事故欠勤日数").unwrap();
+            let mut headings = headings[..offset].to_vec();
+            headings.extend(others.iter().cloned());
+            headings
+        }
+        None => headings,
+    }
+}
+
 pub fn get_csv_headings() -> &'static str {
-    "社員コード,氏名,要勤務日数,要勤務時間,出勤日数,出勤時間,補正出勤時間,出勤時刻差分,法定外労働時間,事故欠勤日数,病気欠勤日数,代休特休日数,休日出勤日数,有休消化日数,有休残日数,残業平日普通,残業平日深夜,残業休日普通,残業休日深夜,残業法定普通,残業法定深夜,遅刻早退回数,遅刻早退時間,有休日数消化,有休時間消化,有休日数残,有休時間残,有休可能時間,残業平日普通45下,残業平日普通45超,残業平日普通60超,残業平日普通代休,残業平日深夜45下,残業平日深夜45超,残業平日深夜60超,残業平日深夜代休,残業休日普通45下,残業休日普通45超,残業休日普通60超,残業休日普通代休,残業休日深夜45下,残業休日深夜45超,残業休日深夜60超,残業休日深夜代休,勤怠自由時間1,勤怠自由時間2,勤怠自由時間3,勤怠自由時間4,勤怠自由時間5,勤怠自由時間6,勤怠自由時間7,勤怠自由時間8,勤怠自由時間9,勤怠自由時間10,勤怠自由数値1,勤怠自由数値2,勤怠自由数値3,勤怠自由数値4,勤怠自由数値5,勤怠自由数値6,勤怠自由数値7,勤怠自由数値8,勤怠自由数値9,勤怠自由数値10,回数1,回数2,回数3,回数4,回数5,回数6,回数7,回数8,回数9,回数10,回数11,回数12,回数13,回数14,回数15,回数16,回数17,回数18,回数19,回数20,回数21,回数22,回数23,回数24,回数25,回数26,回数27,回数28,回数29,回数30"
+    "社員コード,氏名,要勤務日数,要勤務時間,出勤日数,出勤時間,補正出勤時間,出勤時刻差分,法定外労働時間,法定内残業時間,深夜労働時間,事故欠勤日数,病気欠勤日数,代休特休日数,休日出勤日数,有休消化日数,有休残日数,残業平日普通,残業平日深夜,残業休日普通,残業休日深夜,残業法定普通,残業法定深夜,遅刻早退回数,遅刻早退時間,有休日数消化,有休時間消化,有休日数残,有休時間残,有休可能時間,残業平日普通45下,残業平日普通45超,残業平日普通60超,残業平日普通代休,残業平日深夜45下,残業平日深夜45超,残業平日深夜60超,残業平日深夜代休,残業休日普通45下,残業休日普通45超,残業休日普通60超,残業休日普通代休,残業休日深夜45下,残業休日深夜45超,残業休日深夜60超,残業休日深夜代休,勤怠自由時間1,勤怠自由時間2,勤怠自由時間3,勤怠自由時間4,勤怠自由時間5,勤怠自由時間6,勤怠自由時間7,勤怠自由時間8,勤怠自由時間9,勤怠自由時間10,勤怠自由数値1,勤怠自由数値2,勤怠自由数値3,勤怠自由数値4,勤怠自由数値5,勤怠自由数値6,勤怠自由数値7,勤怠自由数値8,勤怠自由数値9,勤怠自由数値10,回数1,回数2,回数3,回数4,回数5,回数6,回数7,回数8,回数9,回数10,回数11,回数12,回数13,回数14,回数15,回数16,回数17,回数18,回数19,回数20,回数21,回数22,回数23,回数24,回数25,回数26,回数27,回数28,回数29,回数30"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::Time;
+    use crate::diagnostics::Diagnostics;
+    use crate::fixtures;
+    use crate::record::DayCounts;
+    use crate::rules::Rules;
+    use crate::total::{collect_from_csv, csv_headings, get_csv_headings, split_by_tiers};
+    use std::collections::HashSet;
+    use std::io::Cursor;
+
+    #[test]
+    fn counted_days() {
+        let rules = include_str!("../設定.toml")
+            .replace("overwrite_days = false", "overwrite_days = true")
+            .parse::<Rules>()
+            .unwrap();
+        let totals = fixtures::convert(
+            &rules,
+            "1,山田太郎,A,\n",
+            "10/4,法定休日\n",
+            "2026/10,1,山田太郎,10/1,木,9:00,17:10,0:10,7:00,,1\n\
+             2026/10,1,山田太郎,10/2,金,,,,,,0\n\
+             2026/10,1,山田太郎,10/4,日,9:00,12:00,,3:00,棚卸,1\n\
+             2026/10,1,山田太郎,10/5,月,,,,,有休,0\n",
+            &(fixtures::totals_headings() + "1,20,160:00,3,10:00,0,0,0,0\n"),
+        )
+        .totals;
+
+        // 10/2は欠勤、10/5は有休なので欠勤に数えない
+        assert_eq!(
+            totals[0].counted_days.peek().unwrap(),
+            &DayCounts {
+                work_days: 1,
+                holiday_work_days: 1,
+                absence_days: 1
+            }
+        );
+        let fields = totals[0].export_to_csv();
+        assert_eq!(fields[4], "1");
+        assert_eq!(fields[11], "1");
+        assert_eq!(fields[14], "1");
+        // 10/1は規定退勤時刻18:00より前に退勤
+        assert_eq!(fields[23], "1");
+        assert_eq!(fields[24], "01.00");
+    }
+
+    #[test]
+    fn night_shift() {
+        let rules = Rules::default_rules().unwrap();
+        let totals = fixtures::convert(
+            &rules,
+            "1,佐藤花子,A,\n",
+            "",
+            "2026/10,1,佐藤花子,10/1,木,21:00,翌6:00,0:00,9:00,,1\n",
+            &(fixtures::totals_headings() + "1,20,160:00,1,9:00\n"),
+        )
+        .totals;
+
+        // 法定外労働時間1時間は勤務の終わり（5:00〜6:00）で、深夜にかからない
+        let headings: Vec<&str> = get_csv_headings().split(",").collect();
+        let fields = totals[0].export_to_csv();
+        let field = |heading: &str| &fields[headings.iter().position(|h| *h == heading).unwrap()];
+        assert_eq!(field("深夜労働時間"), "07.00");
+        assert_eq!(field("残業平日普通"), "01.00");
+        assert_eq!(field("残業平日普通45下"), "01.00");
+        assert_eq!(field("残業平日深夜"), "00.00");
+        assert_eq!(field("残業平日深夜45下"), "00.00");
+    }
+
+    #[test]
+    fn columns_by_heading() {
+        let rules = Rules::default_rules().unwrap();
+        // 氏名の列があり、その他の列の順番も違う
+        let headings = fixtures::totals_headings()
+            .replacen("社員コード,", "社員コード,氏名,", 1)
+            .replacen(
+                "残業平日普通,残業平日深夜,",
+                "残業平日深夜,残業平日普通,",
+                1,
+            );
+        let totals = fixtures::convert(
+            &rules,
+            "1,山田太郎,LUC社員,\n",
+            "",
+            "2026/10,1,山田太郎,10/1,木,8:20,19:40,1:30,9:50,,1\n",
+            &(headings.clone() + "1,山田太郎,20,160:00,1,9:50\n"),
+        )
+        .totals;
+
+        let output = csv_headings(&totals);
+        let expected = get_csv_headings().replacen(
+            "残業平日普通,残業平日深夜,",
+            "残業平日深夜,残業平日普通,",
+            1,
+        );
+        assert_eq!(output.join(","), expected);
+
+        let fields = totals[0].export_to_csv();
+        let field = |heading: &str| &fields[output.iter().position(|h| h == heading).unwrap()];
+        assert_eq!(field("残業平日普通"), "00.30");
+        assert_eq!(field("残業平日深夜"), "00.00");
+        assert_eq!(fields.len(), output.len());
+
+        let mut diagnostics = Diagnostics::new();
+        let missing = headings.replacen(",残業平日普通,", ",", 1) + "1,山田太郎,20,160:00,1,9:50\n";
+        let result = collect_from_csv(
+            Cursor::new(missing),
+            &HashSet::new(),
+            &rules,
+            &mut diagnostics,
+        );
+        assert!(result.unwrap_err().to_string().contains("残業平日普通"));
+    }
+
+    #[test]
+    fn tiers() {
+        let tiers = split_by_tiers(&Time::new(40, 0), &Time::new(10, 0));
+        assert_eq!(tiers[0], ("45下", Time::new(5, 0)));
+        assert_eq!(tiers[1], ("45超", Time::new(5, 0)));
+        assert_eq!(tiers[2], ("60超", Time::new(0, 0)));
+
+        let tiers = split_by_tiers(&Time::new(58, 0), &Time::new(3, 30));
+        assert_eq!(tiers[0], ("45下", Time::new(0, 0)));
+        assert_eq!(tiers[1], ("45超", Time::new(2, 0)));
+        assert_eq!(tiers[2], ("60超", Time::new(1, 30)));
+    }
 }